@@ -0,0 +1,161 @@
+// Compares how long the bots take to think with `Bots::think`, which looks everything up
+// once per tick in a `Snapshot`, against the AI from the original game loop, where every bot
+// went through the whole world and looked up its own cells again for every entity in it.
+// The new AI does more for each bot, intercepting prey and planning escapes, so this
+// is the whole tick's AI then and now, not the same decisions made faster.
+//
+//     $ cargo bench --bench ai
+
+use microbiome::*;
+use microbiome::ai::{Bots, Difficulty, Profile};
+use rayon::prelude::*;
+use std::time::{Duration, Instant};
+
+// How many times to run each tick's AI when timing it
+const ROUNDS: u32 = 20;
+
+fn main() {
+    println!("| Bots | Entities | Before | After | Speedup |");
+    println!("|-----:|---------:|-------:|------:|--------:|");
+    for bots in [10, 20, 40, 80] {
+        let (world, _, _) = Scenario::from_spawn_rules(&SpawnRules { bots, ..SpawnRules::default() }).build();
+        let profiles = world.get_players().into_iter().map(|player| (*player, Profile::random())).collect::<Vec<_>>();
+
+        // Hard bots think on every tick, so every round does the full amount of work
+        let mut brains = Bots::new(Difficulty::Hard);
+        for (player, profile) in &profiles {
+            brains.add(player, profile.clone());
+        }
+
+        let before = time(|| think_like_baseline(&world).len());
+        let after = time(|| brains.think(&world, 0.0).len());
+        println!(
+            "| {} | {} | {:.2} ms | {:.2} ms | {:.1}x |",
+            bots,
+            world.entities.len(),
+            before.as_secs_f64() * 1000.0,
+            after.as_secs_f64() * 1000.0,
+            before.as_secs_f64() / after.as_secs_f64(),
+        );
+    }
+}
+
+// Get the average time one round takes
+fn time(mut round: impl FnMut() -> usize) -> Duration {
+    // Warm up first, so that the thread pool is already running
+    std::hint::black_box(round());
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        std::hint::black_box(round());
+    }
+    start.elapsed() / ROUNDS
+}
+
+// The AI as it was in the original game loop, before it moved into the library. Every bot picks out
+// a target by sorting everyone else with `get_camera_position` and `get_player_cells` in the comparator,
+// then weighs up every entity in the world, looking up its own cells again for each one.
+// Only the decisions are made here; nothing in the world is changed.
+fn think_like_baseline(world: &World) -> Vec<(Player, Direction, Speed, bool)> {
+    let highest_median_mass = world.get_players().into_iter().map(|player| {
+        let mut cells = world.get_player_cells(player);
+        cells.sort_by(|a, b| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal));
+        cells[cells.len() / 2].get_mass().to_area()
+    }).max_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)).unwrap();
+    // The original worked this out every tick too, and never used it
+    std::hint::black_box(world.get_players().into_iter().map(|player| world.get_player_cells(player).len()).sum::<usize>());
+    let highest_population = world.get_players().into_iter()
+        .map(|player| world.get_player_cells(player).len())
+        .max()
+        .unwrap() as f64;
+
+    let players = world.get_players().into_iter().copied().collect::<Vec<_>>();
+    let controls = players.par_iter().map(|player| {
+        let avg_position = world.get_camera_position(player);
+        let target_player = {
+            let mut players = world.get_players().into_iter().copied().filter(|p| p != player).collect::<Vec<_>>();
+            players.sort_by(|a, b| {
+                let a_pos = world.get_camera_position(a);
+                let b_pos = world.get_camera_position(b);
+                let a_mass = world.get_player_cells(a).into_iter().map(|cell| cell.get_mass().to_area()).sum::<f64>() / a_pos.distance_to(avg_position);
+                let b_mass = world.get_player_cells(b).into_iter().map(|cell| cell.get_mass().to_area()).sum::<f64>() / b_pos.distance_to(avg_position);
+                a_mass.partial_cmp(&b_mass).unwrap_or(std::cmp::Ordering::Equal)
+            });
+            players[0]
+        };
+        if world.get_player_cells(&target_player).is_empty() {
+            return None;
+        }
+
+        // The baseline looked these up too, even though only the closest cell was used in the end
+        let by_mass = |a: &&Cell, b: &&Cell| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal);
+        let smallest_cell = world.get_player_cells(&target_player).into_iter().min_by(by_mass).unwrap();
+        std::hint::black_box(world.get_player_cells(&target_player).into_iter().max_by(by_mass));
+        std::hint::black_box(world.get_player_cells(player).into_iter().max_by(by_mass));
+        std::hint::black_box(world.get_player_cells(player).into_iter().min_by(by_mass));
+        let closest_to = |position: Position| world.get_player_cells(player).into_iter().min_by(|a, b| {
+            a.get_position().distance_to(position).partial_cmp(&b.get_position().distance_to(position)).unwrap_or(std::cmp::Ordering::Equal)
+        }).unwrap();
+        std::hint::black_box(closest_to(smallest_cell.get_position()));
+
+        let weighted_directions = world.get_entities().par_iter().filter_map(|(_, entity)| {
+            match entity {
+                Entity::Food(food) => {
+                    let food_mass = food.to_mass().to_area();
+                    let food_pos = food.get_position();
+                    let food_distance = food_pos.distance_to(avg_position);
+                    if food_distance < food.to_mass().to_radius() * 200.0 {
+                        Some((5.0 * food_mass / food_distance.powi(2), closest_to(food_pos).get_position().direction_to(food_pos), false))
+                    } else {
+                        Some((3.0 * food_mass / food_distance.powi(2), avg_position.direction_to(food_pos), false))
+                    }
+                }
+                Entity::Cell(cell) => {
+                    if cell.get_player() == Some(player) {
+                        return None;
+                    }
+                    let cell_pos = cell.get_position();
+                    let cell_mass = cell.get_mass().to_area();
+                    let closest_cell = closest_to(cell_pos);
+                    let closest_cell_mass = closest_cell.get_mass().to_area();
+                    let closest_cell_pos = closest_cell.get_position();
+                    let cell_distance = cell_pos.distance_to(closest_cell_pos);
+                    if cell_distance >= cell.get_mass().to_radius().max(closest_cell.get_mass().to_radius()) * 32.0 {
+                        return None;
+                    }
+                    if cell_mass < closest_cell_mass * 0.3 {
+                        Some((10.0 * cell_mass / cell_distance.powi(2), closest_cell_pos.direction_to(cell_pos), false))
+                    } else if cell_mass < closest_cell_mass * 0.6 {
+                        Some((8.0 * cell_mass / cell_distance.powi(2), closest_cell_pos.direction_to(cell_pos), false))
+                    } else if cell_mass < closest_cell_mass {
+                        Some((6.0 * cell_mass / cell_distance.powi(2), closest_cell_pos.direction_to(cell_pos), false))
+                    } else if cell_mass > closest_cell_mass {
+                        Some((-10.0 * closest_cell_mass / cell_distance.powi(2), closest_cell_pos.direction_to(cell_pos), true))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            }
+        }).collect::<Vec<_>>();
+
+        let (mut x, mut y) = (0.0, 0.0);
+        for (weight, direction, _) in &weighted_directions {
+            x += weight * direction.x_component();
+            y += weight * direction.y_component();
+        }
+        let should_split = weighted_directions.iter().any(|(_, _, should_split)| *should_split);
+        Some((*player, Direction::from_vector(x, y), Speed::default() * 3.5, should_split))
+    }).collect::<Vec<_>>();
+
+    controls.into_iter().flatten().map(|(player, direction, speed, should_split)| {
+        let my_population = world.get_player_cells(&player).len() as f64;
+        let my_median_mass = {
+            let mut cells = world.get_player_cells(&player);
+            cells.sort_by(|a, b| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal));
+            cells[cells.len() / 2].get_mass().to_area()
+        };
+        let split = should_split && my_population < 512.0
+            && (my_median_mass.log10() / highest_median_mass.log10() > 2.0 || my_population.log10() / highest_population.log10() < 0.5);
+        (player, direction, speed, split)
+    }).collect()
+}
@@ -0,0 +1,1141 @@
+use crate::*;
+use serde_derive::{Serialize, Deserialize};
+use rand::rngs::StdRng;
+use std::collections::{HashMap, HashSet};
+use crate::script::{ScriptError, Scripts};
+use rhai::Dynamic;
+
+// How far a player with the default mass can see from the edge of each of its cells.
+// This matches how much of the dish the camera shows a human player.
+const VISION_RADIUS: f64 = 1.0 / 8.0;
+
+// How many buckets the AI's grid has along each side of the arena
+const GRID_COLUMNS: usize = 32;
+
+// How many seconds ahead a bot looks when it can't work out where to intercept its prey
+const MAX_LEAD: f64 = 2.0;
+
+// How many headings a bot tries when it's looking for a way to get away from threats
+const EVASION_HEADINGS: usize = 16;
+
+// A bot splits to get away once every heading leaves a cell this many seconds from being swallowed
+const LAST_RESORT: f64 = 0.5;
+
+// Food further than this from all of a player's cells is lumped together into patches
+const FOOD_DETAIL: f64 = 1.0 / 8.0;
+
+// Why the AI is drawn towards, or pushed away from, something
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Motive {
+    Food,
+    Prey,
+    Threat,
+    // Prey close enough to split onto and swallow
+    Attack,
+    // The way a cell splits off to get away from a threat that's about to swallow it
+    Escape,
+}
+
+// One of the weighted directions that the AI adds up to decide where to go
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Contribution {
+    pub motive: Motive,
+    // The position the direction is measured from
+    pub origin: Position,
+    pub direction: Direction,
+    // Negative weights push the player away instead of pulling them closer
+    pub weight: f64,
+}
+
+// How an AI player weighs up what's around it. Each weight is multiplied by the mass
+// of the food or cell it's about, and divided by the square of the distance to it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    // Food close enough for one of the player's cells to go after, and food further away
+    pub near_food: f64,
+    pub far_food: f64,
+    // Prey under 30%, 60% and 100% of the mass of the player's closest cell
+    pub small_prey: f64,
+    pub medium_prey: f64,
+    pub large_prey: f64,
+    // How strongly bigger cells push the player away
+    pub threat: f64,
+    // How fast the player steers its cells, as a multiple of the default speed
+    pub speed: f64,
+    // A player only splits to chase prey if the log of its median cell mass is this many times
+    // the highest of anyone's, or the log of its cell count is under this fraction of the highest
+    pub split_mass_ratio: f64,
+    pub split_population_ratio: f64,
+    // Whether the player splits to chase any prey, and not just prey it can swallow straight away
+    pub split_on_prey: bool,
+    // How far ahead the player aims when chasing prey, from 0 to aim straight at it,
+    // to 1 to aim where the prey will be when the player catches up
+    pub interception: f64,
+    // Whether the player splits onto prey that its split-off cells could swallow
+    pub split_attack: bool,
+    // How many seconds ahead the player looks when it's picking a way to run from threats
+    pub evasion_horizon: f64,
+    // The name of a loaded script that decides for the player instead, whenever it returns an order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile {
+            name: "Balanced".to_string(),
+            near_food: 5.0,
+            far_food: 3.0,
+            small_prey: 10.0,
+            medium_prey: 8.0,
+            large_prey: 6.0,
+            threat: 10.0,
+            speed: 3.5,
+            split_mass_ratio: 2.0,
+            split_population_ratio: 0.5,
+            split_on_prey: false,
+            interception: 1.0,
+            split_attack: true,
+            evasion_horizon: 2.0,
+            script: None,
+        }
+    }
+}
+
+impl Profile {
+    // Get every built-in profile
+    pub fn builtin() -> Vec<Profile> {
+        vec![
+            Profile::default(),
+            // Sticks to eating food, and leaves other players alone
+            Profile {
+                name: "Passive Grazer".to_string(),
+                near_food: 8.0,
+                far_food: 5.0,
+                small_prey: 2.0,
+                medium_prey: 1.0,
+                large_prey: 0.0,
+                speed: 3.0,
+                interception: 0.5,
+                split_attack: false,
+                ..Profile::default()
+            },
+            // Goes after other players, and only eats food when there's nothing better around
+            Profile {
+                name: "Hunter".to_string(),
+                near_food: 2.0,
+                far_food: 1.0,
+                small_prey: 15.0,
+                medium_prey: 12.0,
+                large_prey: 9.0,
+                threat: 8.0,
+                ..Profile::default()
+            },
+            // Runs from anything bigger long before it gets close
+            Profile {
+                name: "Coward".to_string(),
+                small_prey: 4.0,
+                medium_prey: 2.0,
+                large_prey: 0.0,
+                threat: 25.0,
+                speed: 4.0,
+                split_attack: false,
+                evasion_horizon: 4.0,
+                ..Profile::default()
+            },
+            // Splits at every opportunity, to escape and to attack
+            Profile {
+                name: "Splitter".to_string(),
+                split_mass_ratio: 1.2,
+                split_population_ratio: 0.9,
+                split_on_prey: true,
+                ..Profile::default()
+            },
+        ]
+    }
+
+    // Find a built-in profile by name, ignoring case
+    pub fn find(name: &str) -> Option<Profile> {
+        Profile::builtin().into_iter().find(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    // Get a built-in profile by name, or else load a profile from a file
+    pub fn load(name_or_path: &str) -> Result<Profile, ProfileError> {
+        match Profile::find(name_or_path) {
+            Some(profile) => Ok(profile),
+            None => Ok(Profile::from_json(&std::fs::read_to_string(name_or_path)?)?),
+        }
+    }
+
+    pub fn random() -> Profile {
+        let mut profiles = Profile::builtin();
+        let i = with_rng(|rng| rng.gen_range(0..profiles.len()));
+        profiles.swap_remove(i)
+    }
+
+    // Get the profile's numbers in a fixed order, for tools that tune them. Switches are 0 or 1.
+    pub fn get_genes(&self) -> Vec<f64> {
+        vec![
+            self.near_food,
+            self.far_food,
+            self.small_prey,
+            self.medium_prey,
+            self.large_prey,
+            self.threat,
+            self.speed,
+            self.split_mass_ratio,
+            self.split_population_ratio,
+            self.split_on_prey as u8 as f64,
+            self.interception,
+            self.split_attack as u8 as f64,
+            self.evasion_horizon,
+        ]
+    }
+
+    // Make a copy of the profile with its numbers replaced, in the same order as `get_genes`.
+    // Numbers that can't go negative are kept at zero or more, and switches are on above one half.
+    pub fn with_genes(&self, genes: &[f64]) -> Profile {
+        let gene = |i: usize, fallback: f64| genes.get(i).copied().filter(|gene| gene.is_finite()).unwrap_or(fallback).max(0.0);
+        Profile {
+            name: self.name.clone(),
+            near_food: gene(0, self.near_food),
+            far_food: gene(1, self.far_food),
+            small_prey: gene(2, self.small_prey),
+            medium_prey: gene(3, self.medium_prey),
+            large_prey: gene(4, self.large_prey),
+            threat: gene(5, self.threat),
+            speed: gene(6, self.speed),
+            split_mass_ratio: gene(7, self.split_mass_ratio),
+            split_population_ratio: gene(8, self.split_population_ratio),
+            split_on_prey: gene(9, self.split_on_prey as u8 as f64) > 0.5,
+            interception: gene(10, self.interception).min(1.0),
+            split_attack: gene(11, self.split_attack as u8 as f64) > 0.5,
+            evasion_horizon: gene(12, self.evasion_horizon),
+            script: self.script.clone(),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Profile, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("profiles can always be serialized")
+    }
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl std::fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProfileError::Io(err) => write!(f, "not a built-in profile, and could not read profile: {}", err),
+            ProfileError::Json(err) => write!(f, "could not parse profile: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+impl From<std::io::Error> for ProfileError {
+    fn from(err: std::io::Error) -> Self {
+        ProfileError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for ProfileError {
+    fn from(err: serde_json::Error) -> Self {
+        ProfileError::Json(err)
+    }
+}
+
+// How hard the bots are to beat
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn all() -> [Difficulty; 3] {
+        [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard]
+    }
+
+    // Find a difficulty by name, ignoring case
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        Difficulty::all().into_iter().find(|difficulty| difficulty.get_name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    // How many seconds a bot waits before it changes its mind
+    pub fn get_reaction_delay(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 0.15,
+            Difficulty::Hard => 0.0,
+        }
+    }
+
+    // How far a bot can see, as a multiple of the vision radius for its mass
+    pub fn get_vision_scale(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.5,
+        }
+    }
+
+    // How many seconds a bot remembers a threat after losing sight of it, or None to forget straight away
+    pub fn get_threat_memory(&self) -> Option<f64> {
+        match self {
+            Difficulty::Easy => None,
+            Difficulty::Normal => Some(1.0),
+            Difficulty::Hard => Some(3.0),
+        }
+    }
+
+    // How far each weight can be off from what the bot's profile says, as a fraction of the weight
+    pub fn get_weight_noise(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 0.15,
+            Difficulty::Hard => 0.0,
+        }
+    }
+
+    // How much faster or slower the bots steer than their profiles say
+    pub fn get_speed_scale(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.15,
+        }
+    }
+}
+
+// What the bots need to know about one player, worked out once per tick
+#[derive(Clone, Debug)]
+pub struct Summary<'a> {
+    pub player: Player,
+    pub cells: Vec<&'a Cell>,
+    // The center of mass of the player's cells
+    pub center: Position,
+    // The total area of the player's cells
+    pub mass: f64,
+    pub median_mass: f64,
+    // How far the edge of the furthest cell is from the center
+    pub spread: f64,
+}
+
+// All of the food in one of the grid's buckets, lumped together as if it were one big piece
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Patch {
+    // The food's center of mass
+    pub position: Position,
+    pub mass: Mass,
+    pub count: usize,
+}
+
+// The world as the bots see it on one tick: every player's cells gathered up once,
+// and the food and cells sorted into grids, so that each bot only has to look
+// at what's around it instead of going through the whole world
+#[derive(Clone, Debug)]
+pub struct Snapshot<'a> {
+    pub world: &'a World,
+    players: HashMap<ID, Summary<'a>>,
+    food: Grid<(ID, &'a Entity)>,
+    cells: Grid<(ID, &'a Entity)>,
+    // The food in each of the food grid's buckets, or None for buckets without any
+    patches: Vec<Option<Patch>>,
+    walls: Vec<&'a Wall>,
+}
+
+impl<'a> Snapshot<'a> {
+    pub fn new(world: &'a World) -> Snapshot<'a> {
+        let mut food_grid = Grid::new(world.get_bounds(), GRID_COLUMNS);
+        let mut cell_grid = Grid::new(world.get_bounds(), GRID_COLUMNS);
+        let mut patches = vec![None::<Patch>; food_grid.get_bucket_count()];
+        let mut cells = HashMap::<ID, Vec<&Cell>>::new();
+        let mut walls = Vec::new();
+        for (id, entity) in world.get_entities() {
+            match entity {
+                Entity::Cell(cell) => {
+                    if let Some(player_id) = cell.get_player_id() {
+                        cells.entry(player_id).or_default().push(cell);
+                    }
+                    cell_grid.insert(cell.get_position(), (id, entity));
+                }
+                Entity::Food(food) => {
+                    // Add up the food's mass, and its position weighted by its mass, to find the center later
+                    let weighted = food.get_position() * food.to_mass().to_area();
+                    let patch = patches[food_grid.get_bucket(food.get_position())].get_or_insert(Patch { position: Position(0.0, 0.0), mass: Mass(0.0), count: 0 });
+                    patch.position = patch.position + weighted;
+                    patch.mass = patch.mass + food.to_mass();
+                    patch.count += 1;
+                    food_grid.insert(food.get_position(), (id, entity));
+                }
+                Entity::Wall(wall) => walls.push(wall),
+            }
+        }
+        for patch in patches.iter_mut().flatten() {
+            patch.position = patch.position * (1.0 / patch.mass.to_area());
+        }
+
+        let players = world.get_players().into_iter().filter_map(|player| {
+            let mut cells = cells.remove(&player.get_id())?;
+            cells.sort_by(|a, b| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal));
+            let mass = cells.iter().map(|cell| cell.get_mass().to_area()).sum::<f64>();
+            let center = cells.iter().fold(Position(0.0, 0.0), |total, cell| total + cell.get_position() * cell.get_mass().to_area()) * (1.0 / mass);
+            let spread = cells.iter().map(|cell| cell.get_position().distance_to(center) + cell.get_radius()).fold(0.0, f64::max);
+            let median_mass = cells[cells.len() / 2].get_mass().to_area();
+            Some((player.get_id(), Summary { player: *player, cells, center, mass, median_mass, spread }))
+        }).collect();
+
+        Snapshot { world, players, food: food_grid, cells: cell_grid, patches, walls }
+    }
+
+    // Get a player's summary, or None if they have no cells
+    pub fn get_summary(&self, player: &Player) -> Option<&Summary<'a>> {
+        self.players.get(&player.get_id())
+    }
+
+    pub fn get_summaries(&self) -> impl Iterator<Item = &Summary<'a>> {
+        self.players.values()
+    }
+
+    // Get every food and cell within a radius of a position
+    pub fn query(&self, position: Position, radius: f64) -> impl Iterator<Item = (ID, &'a Entity)> + '_ {
+        self.food.query(position, radius).chain(self.cells.query(position, radius)).map(|(_, entity)| *entity)
+    }
+}
+
+// Where an AI player last saw a threat that has since gone out of sight
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sighting {
+    pub id: ID,
+    pub position: Position,
+    pub mass: Mass,
+    // How many seconds ago it was seen
+    pub age: f64,
+}
+
+// Everything an AI player knows about the world: its own cells, whatever is within
+// its vision radius of them, and the threats it remembers seeing
+#[derive(Clone, Debug)]
+pub struct Perception<'a> {
+    pub player: Player,
+    pub cells: Vec<&'a Cell>,
+    // The player's center of mass, which its vision radius is measured around
+    pub center: Position,
+    pub radius: f64,
+    // Half the width of the arena
+    pub bounds: f64,
+    // Food close to the player's cells, one piece at a time
+    pub food: Vec<&'a Food>,
+    // Food further away, in patches
+    pub patches: Vec<Patch>,
+    // Every other player's cells in sight, including teammates
+    pub others: Vec<(ID, &'a Cell)>,
+    pub walls: Vec<&'a Wall>,
+    pub remembered: Vec<Sighting>,
+}
+
+impl<'a> Perception<'a> {
+    // Look around a player, with its vision scaled up or down, or None if it has no cells
+    pub fn new(snapshot: &Snapshot<'a>, player: &Player, scale: f64) -> Option<Perception<'a>> {
+        let summary = snapshot.get_summary(player)?;
+
+        // Bigger players see further, the same way the camera zooms out for them
+        let radius = VISION_RADIUS * (summary.mass / Mass::default().to_area()).powf(0.25) * scale;
+        let mut perception = Perception {
+            player: *player,
+            cells: summary.cells.clone(),
+            center: summary.center,
+            radius,
+            bounds: snapshot.world.get_bounds(),
+            food: Vec::new(),
+            patches: Vec::new(),
+            others: Vec::new(),
+            walls: Vec::new(),
+            remembered: Vec::new(),
+        };
+
+        let range = summary.spread + radius;
+        perception.walls = snapshot.walls.iter().copied().filter(|wall| wall.get_position().distance_to(summary.center) < range + wall.get_radius()).collect();
+
+        // A patch only stands in for its food when every piece of it is far enough away
+        let detail = FOOD_DETAIL + snapshot.food.get_bucket_size() * std::f64::consts::SQRT_2;
+        for (bucket, items) in snapshot.food.get_buckets(summary.center, range) {
+            let Some(patch) = snapshot.patches[bucket] else {
+                continue;
+            };
+            if perception.closest_cell_to(patch.position).get_position().distance_to(patch.position) > detail {
+                if perception.can_see(patch.position) {
+                    perception.patches.push(patch);
+                }
+                continue;
+            }
+            for (position, (_, entity)) in items {
+                if let Entity::Food(food) = entity {
+                    if perception.can_see(*position) {
+                        perception.food.push(food);
+                    }
+                }
+            }
+        }
+        for (position, (id, entity)) in snapshot.cells.query(summary.center, range) {
+            if let Entity::Cell(cell) = entity {
+                if cell.get_player_id() != Some(player.get_id()) && perception.can_see(*position) {
+                    perception.others.push((*id, cell));
+                }
+            }
+        }
+        Some(perception)
+    }
+
+    // Check if a position is within the vision radius of any of the player's cells
+    pub fn can_see(&self, position: Position) -> bool {
+        self.cells.iter().any(|cell| cell.get_position().distance_to(position) < self.radius + cell.get_radius())
+    }
+
+    // Get the player's cell closest to a position
+    pub fn closest_cell_to(&self, position: Position) -> &'a Cell {
+        self.cells.iter().copied().min_by(|a, b| {
+            let a_distance = a.get_position().distance_to(position);
+            let b_distance = b.get_position().distance_to(position);
+            a_distance.partial_cmp(&b_distance).unwrap_or(std::cmp::Ordering::Equal)
+        }).unwrap()
+    }
+
+    // Remember the threats in sight, and recall the ones that have gone out of sight
+    // in the last `duration` seconds. Threats are forgotten early if the player looks
+    // where they were last seen and they aren't there anymore.
+    pub fn remember(&mut self, memory: &mut Vec<Sighting>, duration: f64) {
+        let smallest_cell_mass = self.cells.iter().map(|cell| cell.get_mass().to_area()).fold(f64::INFINITY, f64::min);
+        let in_sight = self.others.iter().map(|(id, _)| *id).collect::<HashSet<_>>();
+        memory.retain(|sighting| sighting.age < duration && !in_sight.contains(&sighting.id) && !self.can_see(sighting.position));
+
+        for (id, cell) in &self.others {
+            if !cell.get_player().is_some_and(|owner| owner.is_ally_of(&self.player)) && cell.get_mass().to_area() > smallest_cell_mass {
+                memory.push(Sighting { id: *id, position: cell.get_position(), mass: cell.get_mass(), age: 0.0 });
+            }
+        }
+        self.remembered = memory.iter().filter(|sighting| !in_sight.contains(&sighting.id)).copied().collect();
+    }
+}
+
+// One cell or piece of food, copied out of the world for something outside the game to look at
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Body {
+    pub position: Position,
+    // The body's area
+    pub mass: f64,
+    pub radius: f64,
+    pub direction: Direction,
+    // How far the body moves per second
+    pub speed: f64,
+    // Whether the body belongs to the player or one of its teammates
+    pub ally: bool,
+}
+
+impl Body {
+    fn from_cell(cell: &Cell, player: &Player) -> Body {
+        Body {
+            position: cell.get_position(),
+            mass: cell.get_mass().to_area(),
+            radius: cell.get_radius(),
+            direction: cell.get_direction(),
+            speed: cell.get_mass().calculate_slowness(cell.get_speed()).to_speed(),
+            ally: cell.get_player().is_some_and(|owner| owner.is_ally_of(player)),
+        }
+    }
+
+    fn from_food(position: Position, mass: Mass) -> Body {
+        Body { position, mass: mass.to_area(), radius: mass.to_radius(), direction: Direction::default(), speed: 0.0, ally: false }
+    }
+}
+
+// A read-only copy of everything a bot can see, which can be handed to a bot written
+// outside of Rust without giving it the world itself
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sight {
+    // Half the width of the arena
+    pub bounds: f64,
+    pub center: Position,
+    pub radius: f64,
+    pub cells: Vec<Body>,
+    // Food in sight, with far away patches of food as single pieces
+    pub food: Vec<Body>,
+    pub others: Vec<Body>,
+}
+
+impl From<&Perception<'_>> for Sight {
+    fn from(perception: &Perception) -> Self {
+        let player = &perception.player;
+        Sight {
+            bounds: perception.bounds,
+            center: perception.center,
+            radius: perception.radius,
+            cells: perception.cells.iter().map(|cell| Body::from_cell(cell, player)).collect(),
+            food: perception.food.iter().map(|food| Body::from_food(food.get_position(), food.to_mass()))
+                .chain(perception.patches.iter().map(|patch| Body::from_food(patch.position, patch.mass)))
+                .collect(),
+            others: perception.others.iter().map(|(_, cell)| Body::from_cell(cell, player)).collect(),
+        }
+    }
+}
+
+// What a bot written outside of Rust wants to do, given what it can see
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Order {
+    pub direction: Direction,
+    // How fast to steer, as a multiple of the default speed
+    pub speed: f64,
+    #[serde(default)]
+    pub split: bool,
+}
+
+impl Order {
+    // Turn the order into a decision for a player, keeping the speed to something a bot could pick
+    pub fn to_decision(&self, player: &Player, profile: &Profile) -> Decision {
+        let speed = if self.speed.is_finite() { self.speed.clamp(0.0, profile.speed) } else { 0.0 };
+        Decision {
+            player: *player,
+            direction: self.direction,
+            speed: Speed::default() * speed,
+            split: self.split,
+            contributions: Vec::new(),
+        }
+    }
+}
+
+// What an AI player decided to do this tick, and why
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Decision {
+    pub player: Player,
+    pub direction: Direction,
+    pub speed: Speed,
+    pub split: bool,
+    pub contributions: Vec<Contribution>,
+}
+
+// An AI player's profile, and what it's currently set on doing
+#[derive(Clone, Debug)]
+struct Brain {
+    profile: Profile,
+    decision: Option<Decision>,
+    // Threats the player has seen recently
+    memory: Vec<Sighting>,
+    // How many seconds until the player thinks again
+    cooldown: f64,
+    // Each bot has its own random numbers, so that bots thinking in parallel still play out the same way every time
+    rng: StdRng,
+    // What the bot's script keeps from one tick to the next, and the last thing that went wrong with it
+    state: Dynamic,
+    error: Option<ScriptError>,
+}
+
+// Every AI player in a match
+#[derive(Clone, Debug, Default)]
+pub struct Bots {
+    difficulty: Difficulty,
+    brains: Map<ID, Brain>,
+    scripts: Scripts,
+    // Script errors that haven't been handed out yet, and every one there's been, so each is only reported once
+    errors: Vec<ScriptError>,
+    reported: HashSet<ScriptError>,
+}
+
+impl Bots {
+    pub fn new(difficulty: Difficulty) -> Bots {
+        Bots {
+            difficulty,
+            brains: Map::default(),
+            scripts: Scripts::new(),
+            errors: Vec::new(),
+            reported: HashSet::new(),
+        }
+    }
+
+    pub fn get_difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    // Let the AI control a player
+    pub fn add(&mut self, player: &Player, profile: Profile) {
+        let rng = with_rng(|rng| StdRng::seed_from_u64(rng.gen()));
+        let state = Dynamic::from_map(rhai::Map::new());
+        self.brains.insert(player.get_id(), Brain { profile, decision: None, memory: Vec::new(), cooldown: 0.0, rng, state, error: None });
+    }
+
+    // Take a player back from the AI
+    pub fn remove(&mut self, player: &Player) {
+        self.brains.remove(&player.get_id());
+    }
+
+    pub fn get_profile(&self, player: &Player) -> Option<&Profile> {
+        self.brains.get(&player.get_id()).map(|brain| &brain.profile)
+    }
+
+    // Use these scripts for the bots whose profiles ask for one
+    pub fn set_scripts(&mut self, scripts: Scripts) {
+        self.scripts = scripts;
+    }
+
+    // Get everything that's gone wrong with the bots' scripts since last time,
+    // leaving out anything that's already been reported
+    pub fn take_errors(&mut self) -> Vec<ScriptError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    // Decide what each bot should do, without changing the world. Bots only change
+    // their minds once their reaction delay is up; until then, they keep going the same way.
+    pub fn think(&mut self, world: &World, seconds_since_last_tick: f64) -> Vec<Decision> {
+        let snapshot = Snapshot::new(world);
+        let highest_median_mass = snapshot.get_summaries().map(|summary| summary.median_mass).fold(0.0, f64::max);
+        let highest_population = snapshot.get_summaries().map(|summary| summary.cells.len()).max().unwrap_or(0) as f64;
+
+        let difficulty = self.difficulty;
+        let scripts = &self.scripts;
+        let players = world.get_players().into_iter().map(|player| (player.get_id(), *player)).collect::<HashMap<_, _>>();
+        let decisions = self.brains.par_iter_mut().filter_map(|(id, brain)| {
+            let player = players.get(id)?;
+            brain.cooldown -= seconds_since_last_tick;
+            for sighting in &mut brain.memory {
+                sighting.age += seconds_since_last_tick;
+            }
+            if brain.cooldown > 0.0 {
+                // Keep doing the same thing, without splitting over and over again
+                let mut decision = brain.decision.clone()?;
+                decision.split = false;
+                return Some(decision);
+            }
+            brain.cooldown = difficulty.get_reaction_delay();
+
+            // There's nothing to do once every enemy is gone
+            let enemies_left = snapshot.get_summaries().any(|other| !other.player.is_ally_of(player));
+            let mut scripted = false;
+            let mut decision = Perception::new(&snapshot, player, difficulty.get_vision_scale())
+                .filter(|_| enemies_left)
+                .and_then(|mut perception| {
+                    match difficulty.get_threat_memory() {
+                        Some(duration) => perception.remember(&mut brain.memory, duration),
+                        None => brain.memory.clear(),
+                    }
+                    // A script that goes wrong, or doesn't say what to do, leaves it to the built-in AI
+                    if let Some(script) = &brain.profile.script {
+                        match scripts.run(script, &Sight::from(&perception), &mut brain.state) {
+                            Ok(Some(order)) => {
+                                scripted = true;
+                                return Some(order.to_decision(player, &brain.profile));
+                            }
+                            Ok(None) => (),
+                            Err(error) => brain.error = Some(error),
+                        }
+                    }
+                    decide(&perception, &brain.profile, difficulty, &mut brain.rng)
+                });
+            if let (Some(decision), Some(summary)) = (&mut decision, snapshot.get_summary(player)) {
+                // Nobody splits unless they're much bigger than everyone else, or far outnumbered,
+                // except to swallow prey they've lined up or to get away from a threat.
+                // Scripts make up their own minds, as long as there's room for more cells.
+                let my_population = summary.cells.len() as f64;
+                let forced = scripted || decision.contributions.iter().any(|contribution| matches!(contribution.motive, Motive::Attack | Motive::Escape));
+                decision.split = decision.split && my_population < 512.0 && (
+                    forced
+                    || summary.median_mass.log10() / highest_median_mass.log10() > brain.profile.split_mass_ratio
+                    || my_population.log10() / highest_population.log10() < brain.profile.split_population_ratio
+                );
+            }
+            brain.decision = decision.clone();
+            decision
+        }).collect();
+
+        // Pass on what went wrong with the scripts, in the same order every time
+        let mut errors = self.brains.values_mut().filter_map(|brain| brain.error.take()).collect::<Vec<_>>();
+        errors.sort_by_key(|error| error.to_string());
+        for error in errors {
+            if self.reported.insert(error.clone()) {
+                self.errors.push(error);
+            }
+        }
+        decisions
+    }
+}
+
+// Carry out the decisions made by `Bots::think`
+pub fn act(world: &mut World, decisions: &[Decision]) {
+    for decision in decisions {
+        world.set_controls(&decision.player, decision.direction, decision.speed);
+        if decision.split {
+            world.mitosis(&decision.player);
+        }
+    }
+}
+
+// Weigh up all of the food, prey and threats a player can see to decide where it should go.
+// The split flag is set whenever the player wants to split, before `Bots::think` decides if it's worth it.
+pub fn decide(perception: &Perception, profile: &Profile, difficulty: Difficulty, rng: &mut impl Rng) -> Option<Decision> {
+    let player = &perception.player;
+    let center = perception.center;
+    let noise = difficulty.get_weight_noise();
+    let speed = Speed::default() * (profile.speed * difficulty.get_speed_scale());
+
+    let mut contributions = perception.food.iter().map(|food| {
+        let food_mass = food.to_mass().to_area();
+        let food_pos = food.get_position();
+        let food_distance = food_pos.distance_to(center);
+
+        // Nearby food is chased by whichever cell is closest to it
+        let (origin, weight) = if food_distance < food.to_mass().to_radius() * 200.0 {
+            (perception.closest_cell_to(food_pos).get_position(), profile.near_food * food_mass / food_distance.powi(2))
+        } else {
+            (center, profile.far_food * food_mass / food_distance.powi(2))
+        };
+        Contribution { motive: Motive::Food, origin, direction: origin.direction_to(food_pos), weight }
+    }).collect::<Vec<_>>();
+
+    // Far away food only counts as a whole patch
+    contributions.extend(perception.patches.iter().map(|patch| {
+        let distance = patch.position.distance_to(center);
+        let weight = profile.far_food * patch.mass.to_area() / distance.powi(2);
+        Contribution { motive: Motive::Food, origin: center, direction: center.direction_to(patch.position), weight }
+    }));
+
+    contributions.extend(perception.others.iter().filter_map(|(_, cell)| {
+        // Teammates are neither prey nor threats
+        if cell.get_player().is_some_and(|owner| owner.is_ally_of(player)) {
+            return None;
+        }
+        let cell_pos = cell.get_position();
+        let cell_mass = cell.get_mass().to_area();
+
+        let closest_cell = perception.closest_cell_to(cell_pos);
+        let closest_cell_mass = closest_cell.get_mass().to_area();
+        let closest_cell_pos = closest_cell.get_position();
+        let cell_distance = cell_pos.distance_to(closest_cell_pos);
+        let is_close = cell_distance < cell.get_mass().to_radius().max(closest_cell.get_mass().to_radius()) * 32.0;
+        if !is_close {
+            return None;
+        }
+
+        // The smaller the prey, the more it's worth chasing
+        let (motive, weight) = if cell_mass < closest_cell_mass * 0.3 {
+            (Motive::Prey, profile.small_prey * cell_mass / cell_distance.powi(2))
+        } else if cell_mass < closest_cell_mass * 0.6 {
+            (Motive::Prey, profile.medium_prey * cell_mass / cell_distance.powi(2))
+        } else if cell_mass < closest_cell_mass {
+            (Motive::Prey, profile.large_prey * cell_mass / cell_distance.powi(2))
+        } else if cell_mass > closest_cell_mass {
+            (Motive::Threat, -profile.threat * closest_cell_mass / cell_distance.powi(2))
+        } else {
+            return None;
+        };
+        if motive == Motive::Threat {
+            return Some(Contribution { motive, origin: closest_cell_pos, direction: closest_cell_pos.direction_to(cell_pos), weight });
+        }
+
+        // Head for where the prey is going to be, rather than where it is now
+        let chase_speed = closest_cell.get_mass().calculate_slowness(speed).to_speed();
+        let aim = intercept(closest_cell_pos, chase_speed, cell, perception.bounds);
+        let aim = cell_pos + (aim - cell_pos) * profile.interception.clamp(0.0, 1.0);
+        let motive = if profile.split_attack && can_split_onto(closest_cell, cell, aim) { Motive::Attack } else { motive };
+        Some(Contribution { motive, origin: closest_cell_pos, direction: closest_cell_pos.direction_to(aim), weight })
+    }));
+
+    // Threats that went out of sight are still avoided wherever they were last seen
+    contributions.extend(perception.remembered.iter().filter_map(|sighting| {
+        let closest_cell = perception.closest_cell_to(sighting.position);
+        let closest_cell_mass = closest_cell.get_mass().to_area();
+        let closest_cell_pos = closest_cell.get_position();
+        if sighting.mass.to_area() <= closest_cell_mass {
+            return None;
+        }
+        let weight = -profile.threat * closest_cell_mass / sighting.position.distance_to(closest_cell_pos).powi(2);
+        Some(Contribution { motive: Motive::Threat, origin: closest_cell_pos, direction: closest_cell_pos.direction_to(sighting.position), weight })
+    }));
+
+    // Sloppier bots misjudge how much everything matters
+    if noise > 0.0 {
+        for contribution in &mut contributions {
+            contribution.weight *= 1.0 + rng.gen_range(-1.0..1.0) * noise;
+        }
+    }
+
+    let mut x = 0.0;
+    let mut y = 0.0;
+    for contribution in &contributions {
+        x += contribution.weight * contribution.direction.x_component();
+        y += contribution.weight * contribution.direction.y_component();
+    }
+
+    // When a threat is closing in, pick the best way to run instead of just being pushed away from each one.
+    // That way is only worth splitting along as a last resort, when even it leaves a cell about to be swallowed.
+    let mut evasion = None;
+    if contributions.iter().any(|contribution| contribution.motive == Motive::Threat) {
+        let (x, y) = contributions.iter()
+            .filter(|contribution| contribution.motive != Motive::Threat)
+            .fold((0.0, 0.0), |(x, y), contribution| (x + contribution.weight * contribution.direction.x_component(), y + contribution.weight * contribution.direction.y_component()));
+        let preferred = if x == 0.0 && y == 0.0 { None } else { Some(Direction::from_vector(x, y)) };
+        if let Some(escape) = evade(perception, speed, profile.evasion_horizon, preferred) {
+            if escape.time < LAST_RESORT {
+                let origin = escape.cell.get_position();
+                contributions.push(Contribution { motive: Motive::Escape, origin, direction: escape.direction, weight: profile.threat });
+            }
+            evasion = Some(escape.direction);
+        }
+    }
+
+    // With a split attack lined up, head straight for the best target, so that the split-off cells fly at it
+    let attack = contributions.iter()
+        .filter(|contribution| contribution.motive == Motive::Attack)
+        .max_by(|a, b| a.weight.partial_cmp(&b.weight).unwrap_or(std::cmp::Ordering::Equal));
+    let direction = if let Some(direction) = evasion {
+        direction
+    } else if let Some(attack) = attack {
+        attack.direction
+    } else if contributions.is_empty() {
+        // With nothing in sight, wander back towards the middle of the dish
+        center.direction_to(Position(0.0, 0.0))
+    } else {
+        Direction::from_vector(x, y)
+    };
+
+    Some(Decision {
+        player: *player,
+        direction,
+        speed,
+        split: contributions.iter().any(|contribution| match contribution.motive {
+            Motive::Attack | Motive::Escape => true,
+            Motive::Prey => profile.split_on_prey,
+            Motive::Food | Motive::Threat => false,
+        }),
+        contributions,
+    })
+}
+
+// The way a player picked to run from the threats around it
+#[derive(Clone, Copy, Debug)]
+pub struct Escape<'a> {
+    pub direction: Direction,
+    // How many seconds until a threat could swallow one of the player's cells going this way
+    pub time: f64,
+    // The cell that's in the most danger
+    pub cell: &'a Cell,
+}
+
+// Try out a spread of headings to find the one that keeps a player's cells away from every threat
+// for the longest, without running into the edges of the arena or walls. Headings closer to the
+// preferred direction are picked when it's a close call. Returns None if no threat is close
+// enough to catch any of the player's cells within `horizon` seconds going the preferred way.
+pub fn evade<'a>(perception: &Perception<'a>, speed: Speed, horizon: f64, preferred: Option<Direction>) -> Option<Escape<'a>> {
+    // Every threat's position, velocity and mass, with the ones the player remembers standing still
+    let threats = perception.others.iter()
+        .filter(|(_, cell)| !cell.get_player().is_some_and(|owner| owner.is_ally_of(&perception.player)))
+        .map(|(_, cell)| (cell.get_position(), cell.get_mass().calculate_slowness(cell.get_speed()).to_vector(&cell.get_direction()), cell.get_mass()))
+        .chain(perception.remembered.iter().map(|sighting| (sighting.position, (0.0, 0.0), sighting.mass)))
+        .collect::<Vec<_>>();
+
+    // Score a heading by how long until the first cell could be swallowed and how long until the first one hits a wall,
+    // returning both scores and the cell in the most danger
+    let score = |direction: Direction| {
+        let mut danger = (horizon, perception.cells[0]);
+        let mut clearance = horizon;
+        for cell in &perception.cells {
+            let velocity = cell.get_mass().calculate_slowness(speed).to_vector(&direction);
+            for (position, (vx, vy), mass) in &threats {
+                if mass.to_area() <= cell.get_mass().to_area() * 1.1 {
+                    continue;
+                }
+                // A threat swallows a cell once the cell is two thirds of the way inside it
+                let reach = mass.to_radius() + cell.get_radius() * 2.0 / 3.0;
+                let time = time_to_contact(*position - cell.get_position(), (vx - velocity.0, vy - velocity.1), reach);
+                if time < danger.0 {
+                    danger = (time, cell);
+                }
+            }
+            for wall in &perception.walls {
+                let time = time_to_contact(wall.get_position() - cell.get_position(), (-velocity.0, -velocity.1), wall.get_radius() + cell.get_radius());
+                clearance = clearance.min(time);
+            }
+            clearance = clearance.min(time_to_edge(cell.get_position(), velocity, perception.bounds));
+        }
+        (danger, clearance)
+    };
+
+    let preferred_direction = preferred.unwrap_or_else(|| perception.center.direction_to(Position(0.0, 0.0)));
+    if score(preferred_direction).0.0 >= horizon {
+        return None;
+    }
+
+    (0..EVASION_HEADINGS)
+        .map(|i| Direction::from_radians(2.0 * std::f64::consts::PI * i as f64 / EVASION_HEADINGS as f64))
+        .chain(std::iter::once(preferred_direction))
+        .map(|direction| {
+            let ((time, cell), clearance) = score(direction);
+            // Staying away from threats matters most, then room to keep running, then going where the player wanted
+            let alignment = preferred.map_or(0.0, |preferred| (direction.to_radians() - preferred.to_radians()).cos() + 1.0);
+            let value = time + clearance * 0.5 + alignment * horizon * 0.1;
+            (value, Escape { direction, time, cell })
+        })
+        .max_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(_, escape)| escape)
+}
+
+// Get how many seconds until something at `offset` from a point, moving at `velocity` relative to it,
+// comes within `reach` of it, or infinity if it never does
+fn time_to_contact(offset: Position, (vx, vy): (f64, f64), reach: f64) -> f64 {
+    let Position(rx, ry) = offset;
+    let c = rx * rx + ry * ry - reach * reach;
+    if c <= 0.0 {
+        return 0.0;
+    }
+    let a = vx * vx + vy * vy;
+    let b = 2.0 * (rx * vx + ry * vy);
+    let discriminant = b * b - 4.0 * a * c;
+    if a < f64::EPSILON || b >= 0.0 || discriminant < 0.0 {
+        return f64::INFINITY;
+    }
+    (-b - discriminant.sqrt()) / (2.0 * a)
+}
+
+// Get how many seconds until a cell moving at `velocity` reaches the edge of the arena
+fn time_to_edge(position: Position, (vx, vy): (f64, f64), bounds: f64) -> f64 {
+    let axis = |coordinate: f64, velocity: f64| {
+        if velocity > 0.0 {
+            (bounds - coordinate) / velocity
+        } else if velocity < 0.0 {
+            (-bounds - coordinate) / velocity
+        } else {
+            f64::INFINITY
+        }
+    };
+    axis(position.get_x(), vx).min(axis(position.get_y(), vy))
+}
+
+// Work out where a chaser moving at `speed` (in distance per second) should aim to catch a cell,
+// from the way it's heading and how fast it moves for its mass
+pub fn intercept(from: Position, speed: f64, target: &Cell, bounds: f64) -> Position {
+    let position = target.get_position();
+    let (vx, vy) = target.get_mass().calculate_slowness(target.get_speed()).to_vector(&target.get_direction());
+    let Position(rx, ry) = position - from;
+
+    // Solve |r + v t| = speed * t for the soonest time t when the chaser can get there
+    let a = vx * vx + vy * vy - speed * speed;
+    let b = 2.0 * (rx * vx + ry * vy);
+    let c = rx * rx + ry * ry;
+    let time = if a.abs() < f64::EPSILON {
+        (b < 0.0).then(|| -c / b)
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        (discriminant >= 0.0).then(|| {
+            let root = discriminant.sqrt();
+            [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)].into_iter().filter(|t| *t > 0.0).reduce(f64::min)
+        }).flatten()
+    };
+
+    // When the prey is too fast to catch, just lead it by however long it would take to get where it is now
+    let time = time.unwrap_or(c.sqrt() / speed.max(f64::EPSILON)).min(MAX_LEAD);
+    let Position(x, y) = position + Position(vx, vy) * time;
+    Position(x.clamp(-bounds, bounds), y.clamp(-bounds, bounds))
+}
+
+// Check if splitting would let one of a player's cells swallow a target at the given position:
+// the cell has to be old enough to split, each half has to be big enough to eat the target,
+// and the target has to be within reach of the half that shoots forward
+pub fn can_split_onto(cell: &Cell, target: &Cell, position: Position) -> bool {
+    let half = Mass::from_area(cell.get_mass().to_area() / 2.0);
+    // The split-off cell lands one and a half of the old radius ahead, and has about its own width to close the gap
+    let reach = cell.get_radius() * 1.5 + half.to_radius() * 2.0 + target.get_radius() * 2.0 / 3.0;
+    cell.get_age() > SPLIT_AGE
+        && half.to_area() > target.get_mass().to_area() * 1.1
+        && cell.get_position().distance_to(position) < reach
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(position: Position, direction: Direction, speed: Speed) -> Cell {
+        Cell::new(Mass::default() * 10.0, position, direction, speed, None)
+    }
+
+    #[test]
+    fn contact_when_already_overlapping() {
+        assert_eq!(time_to_contact(Position(0.1, 0.0), (1.0, 0.0), 0.2), 0.0);
+        assert_eq!(time_to_contact(Position(0.0, 0.0), (0.0, 0.0), 0.0), 0.0);
+    }
+
+    #[test]
+    fn contact_head_on_and_moving_apart() {
+        // A unit away, closing at half a unit a second, touching at half a unit apart
+        let time = time_to_contact(Position(1.0, 0.0), (-0.5, 0.0), 0.5);
+        assert!((time - 1.0).abs() < 1e-9);
+        assert_eq!(time_to_contact(Position(1.0, 0.0), (0.5, 0.0), 0.5), f64::INFINITY);
+        assert_eq!(time_to_contact(Position(1.0, 0.0), (0.0, 0.0), 0.5), f64::INFINITY);
+        // Passing by too far to the side to ever touch
+        assert_eq!(time_to_contact(Position(1.0, 1.0), (-1.0, 0.0), 0.5), f64::INFINITY);
+    }
+
+    #[test]
+    fn edge_of_the_arena() {
+        assert!((time_to_edge(Position(0.0, 0.0), (0.5, 0.0), 1.0) - 2.0).abs() < 1e-9);
+        assert!((time_to_edge(Position(0.0, 0.5), (-0.25, 1.0), 1.0) - 0.5).abs() < 1e-9);
+        assert_eq!(time_to_edge(Position(0.0, 0.0), (0.0, 0.0), 1.0), f64::INFINITY);
+    }
+
+    #[test]
+    fn intercept_still_prey() {
+        let prey = cell(Position(0.3, -0.2), Direction::default(), Speed(0.0));
+        let aim = intercept(Position(0.0, 0.0), 0.1, &prey, 1.0);
+        assert!(aim.distance_to(prey.get_position()) < 1e-9);
+    }
+
+    #[test]
+    fn intercept_catchable_prey() {
+        // The chaser and the prey should both reach the aim at the same time
+        let from = Position(0.0, 0.0);
+        let speed = 0.5;
+        let prey = cell(Position(0.5, 0.0), Direction::from_radians(std::f64::consts::FRAC_PI_2), Speed::default());
+        let prey_speed = prey.get_mass().calculate_slowness(prey.get_speed()).to_speed();
+        assert!(prey_speed < speed);
+
+        let aim = intercept(from, speed, &prey, 10.0);
+        let chaser_time = aim.distance_to(from) / speed;
+        let prey_time = aim.distance_to(prey.get_position()) / prey_speed;
+        assert!((chaser_time - prey_time).abs() < 1e-6);
+        assert!(aim.get_y() > 0.0);
+    }
+
+    #[test]
+    fn intercept_prey_too_fast_to_catch() {
+        // Running straight away faster than the chaser can go, so the chaser just leads it
+        let from = Position(0.0, 0.0);
+        let prey = cell(Position(0.1, 0.0), Direction::from_radians(0.0), Speed::default() * 4.0);
+        let (vx, vy) = prey.get_mass().calculate_slowness(prey.get_speed()).to_vector(&prey.get_direction());
+        let speed = vx / 2.0;
+
+        let aim = intercept(from, speed, &prey, 10.0);
+        let lead = (0.1 / speed).min(MAX_LEAD);
+        assert!(aim.distance_to(prey.get_position() + Position(vx, vy) * lead) < 1e-9);
+
+        // And never past the edge of the arena
+        let aim = intercept(from, speed, &prey, 0.2);
+        assert!(aim.get_x() <= 0.2);
+    }
+}
@@ -0,0 +1,397 @@
+// Pits AI profiles against each other in seeded headless matches, and ranks them with Elo
+// ratings worked out from where they placed. Every match seats the entrants in a different
+// order, and the report has a leaderboard, who knocked out whom, and how their mass grew.
+//
+//     $ cargo run --release --bin tournament -- --entrant Hunter --entrant trained.json --matches 50
+//     $ cargo run --release --bin tournament -- --format json --output report.json
+//     $ cargo run --release --bin tournament -- --script greedy --entrant Hunter
+
+use microbiome::*;
+use microbiome::ai::{Difficulty, Profile};
+use microbiome::cli::{self, Args};
+use microbiome::script::Scripts;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use serde_derive::Serialize;
+
+// The rating every entrant starts with
+const STARTING_RATING: f64 = 1500.0;
+
+// How far a single match can move a rating. It's shared out over everyone an entrant played against.
+const K_FACTOR: f64 = 32.0;
+
+// How the report is written out
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Markdown,
+    Json,
+}
+
+// Settings picked on the command line
+#[derive(Clone, Debug)]
+struct Options {
+    entrants: Vec<Profile>,
+    matches: usize,
+    // How many players each match has. Entrants take turns filling the seats.
+    seats: usize,
+    // The longest a match goes on, in seconds of game time
+    duration: f64,
+    // How many seconds of game time there are between samples of everyone's mass
+    interval: f64,
+    seed: u64,
+    difficulty: Difficulty,
+    // The scenario to play instead of the default one, which decides how many seats there are
+    scenario: Option<Scenario>,
+    format: Format,
+    output: Option<String>,
+    // The scripts that entrants' profiles can ask for, if any were loaded
+    scripts: Option<Scripts>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            entrants: Vec::new(),
+            matches: 20,
+            seats: 10,
+            duration: 180.0,
+            interval: 10.0,
+            seed: 0,
+            difficulty: Difficulty::Normal,
+            scenario: None,
+            format: Format::Markdown,
+            output: None,
+            scripts: None,
+        }
+    }
+}
+
+impl Options {
+    fn from_args(args: impl Iterator<Item = String>) -> Options {
+        let mut options = Options::default();
+        let mut args = Args::new(args);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--entrant" => options.entrants.push(args.profile(&arg)),
+                "--script" => {
+                    let name = args.value(&arg, "the name of a script");
+                    options.entrants.push(Profile { name: name.clone(), script: Some(name), ..Profile::default() });
+                }
+                "--scripts" => options.scripts = Some(args.scripts(&arg)),
+                "--matches" => options.matches = args.parse::<usize>(&arg, "a number of matches").max(1),
+                "--seats" => options.seats = args.parse::<usize>(&arg, "a number of players").max(2),
+                "--duration" => options.duration = args.parse(&arg, "a number of seconds"),
+                "--interval" => options.interval = args.parse::<f64>(&arg, "a number of seconds").max(TIME_STEP),
+                "--seed" => options.seed = args.parse(&arg, "a number"),
+                "--difficulty" => options.difficulty = args.difficulty(&arg),
+                "--scenario" => options.scenario = Some(args.scenario(&arg)),
+                "--format" => {
+                    options.format = match args.value(&arg, "markdown or json").to_ascii_lowercase().as_str() {
+                        "markdown" | "md" => Format::Markdown,
+                        "json" => Format::Json,
+                        format => cli::exit(format!("Unknown format {:?}", format)),
+                    }
+                }
+                "--output" => options.output = Some(args.value(&arg, "a path to write the report to")),
+                _ => eprintln!("Ignoring unknown argument {:?}", arg),
+            }
+        }
+
+        // Without any entrants, the built-in profiles play each other
+        if options.entrants.is_empty() {
+            options.entrants = Profile::builtin();
+        }
+        for script in options.entrants.iter().filter_map(|entrant| entrant.script.as_ref()) {
+            cli::require_script(&mut options.scripts, script);
+        }
+        options
+    }
+}
+
+// How one seat did in a match
+#[derive(Clone, Debug, Serialize)]
+struct Seat {
+    // Which entrant was playing, as an index into the leaderboard's entrants
+    entrant: usize,
+    // 1 for the winner. Players knocked out on the same tick share a placement.
+    placement: usize,
+    // Seconds into the match they were knocked out, or None if they lasted to the end
+    eliminated: Option<f64>,
+    // Their mass every `interval` seconds, in multiples of the default mass
+    masses: Vec<f64>,
+    peak_mass: f64,
+    cells_eaten: u32,
+}
+
+// Someone knocking someone else out, with both given as seats in the match
+#[derive(Clone, Copy, Debug, Serialize)]
+struct Kill {
+    killer: Option<usize>,
+    victim: usize,
+    time: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct MatchRecord {
+    seed: u64,
+    duration: f64,
+    seats: Vec<Seat>,
+    kills: Vec<Kill>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Standing {
+    name: String,
+    rating: f64,
+    seats: usize,
+    wins: usize,
+    mean_placement: f64,
+    // The average over every seat the entrant played of how long it lasted, in seconds
+    mean_survival: f64,
+    mean_peak_mass: f64,
+    kills: usize,
+    deaths: usize,
+    // The entrant's average mass at each sample, counting knocked out seats as nothing
+    mass_curve: Vec<f64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Report {
+    seed: u64,
+    difficulty: String,
+    interval: f64,
+    // Standings in the order the entrants were given
+    entrants: Vec<Standing>,
+    // Indices into `entrants`, best rating first
+    leaderboard: Vec<usize>,
+    // How many times each entrant knocked out each other entrant, by killer then victim
+    kill_graph: Vec<Vec<usize>>,
+    matches: Vec<MatchRecord>,
+}
+
+fn main() {
+    let mut options = Options::from_args(std::env::args().skip(1));
+    let scenario = options.scenario.clone().unwrap_or_else(|| {
+        Scenario::from_spawn_rules(&SpawnRules { bots: options.seats - 1, ..SpawnRules::default() })
+    });
+    options.seats = scenario.build().0.get_players().len();
+
+    // Entrants with the same name would be impossible to tell apart in the report
+    let names = options.entrants.iter().enumerate().map(|(i, entrant)| {
+        let count = options.entrants[..i].iter().filter(|other| other.name == entrant.name).count();
+        if count == 0 { entrant.name.clone() } else { format!("{} ({})", entrant.name, count + 1) }
+    }).collect::<Vec<_>>();
+
+    let matches = (0..options.matches).into_par_iter()
+        .map(|i| play(&scenario, options.seed.wrapping_add(i as u64), &options))
+        .collect::<Vec<_>>();
+    let report = summarize(names, matches, &options);
+
+    let text = match options.format {
+        Format::Markdown => to_markdown(&report),
+        Format::Json => serde_json::to_string_pretty(&report).expect("the report can always be written as JSON"),
+    };
+    match &options.output {
+        Some(path) => {
+            if let Err(err) = std::fs::write(path, text) {
+                cli::exit(format!("{}: {}", path, err));
+            }
+            println!("Saved to {}", path);
+        }
+        None => println!("{}", text),
+    }
+}
+
+// Play a match with the entrants seated in an order picked by the seed, so that nobody always
+// gets the same spot, and the same entrant shows up more than once when there are seats to spare
+fn play(scenario: &Scenario, seed: u64, options: &Options) -> MatchRecord {
+    let mut order = (0..options.entrants.len()).collect::<Vec<_>>();
+    order.shuffle(&mut StdRng::seed_from_u64(seed));
+    let entrants = order.iter().cycle().take(options.seats).copied().collect::<Vec<_>>();
+    let profiles = entrants.iter().map(|i| options.entrants[*i].clone()).collect::<Vec<_>>();
+
+    let mut game = Match::new(scenario, options.difficulty, &profiles, seed);
+    game.bots.set_scripts(options.scripts.clone().unwrap_or_default());
+    let roster = game.get_roster().to_vec();
+    let mut eliminated = vec![None; roster.len()];
+    let mut masses = vec![Vec::new(); roster.len()];
+    let mut kills = Vec::new();
+    let mut next_sample = 0.0;
+    loop {
+        if game.get_elapsed() >= next_sample {
+            for (player, masses) in roster.iter().zip(masses.iter_mut()) {
+                masses.push(game.world.get_player_mass(player).0 / Mass::default().0);
+            }
+            next_sample += options.interval;
+        }
+        if game.get_elapsed() >= options.duration || game.is_over() {
+            break;
+        }
+
+        game.step();
+        for error in game.bots.take_errors() {
+            eprintln!("{}", error);
+        }
+        for (seat, player) in roster.iter().enumerate() {
+            if eliminated[seat].is_none() && !game.is_alive(player) {
+                eliminated[seat] = Some(game.get_elapsed());
+                let killer = game.world.get_stats(player).last_eaten_by
+                    .and_then(|id| roster.iter().position(|other| other.get_id() == id));
+                kills.push(Kill { killer, victim: seat, time: game.get_elapsed() });
+            }
+        }
+    }
+
+    // Whoever lasted longest places highest, and everyone still going at the end is ranked by their mass
+    let finals = roster.iter().map(|player| game.world.get_player_mass(player).0).collect::<Vec<_>>();
+    let key = |seat: usize| (eliminated[seat].unwrap_or(f64::INFINITY), finals[seat]);
+    let seats = (0..roster.len()).map(|seat| {
+        let ahead = (0..roster.len()).filter(|other| key(*other) > key(seat)).count();
+        let stats = game.world.get_stats(&roster[seat]);
+        Seat {
+            entrant: entrants[seat],
+            placement: ahead + 1,
+            eliminated: eliminated[seat],
+            masses: std::mem::take(&mut masses[seat]),
+            peak_mass: stats.peak_mass.0 / Mass::default().0,
+            cells_eaten: stats.cells_eaten,
+        }
+    }).collect();
+
+    MatchRecord {
+        seed,
+        duration: game.get_elapsed(),
+        seats,
+        kills,
+    }
+}
+
+// Work out the ratings and totals for every entrant
+fn summarize(names: Vec<String>, matches: Vec<MatchRecord>, options: &Options) -> Report {
+    let count = names.len();
+    let mut ratings = vec![STARTING_RATING; count];
+    for record in &matches {
+        // Every pair of seats played by different entrants counts as a game between them, and
+        // all of a match's changes are worked out from the ratings it started with
+        let mut changes = vec![0.0; count];
+        for a in &record.seats {
+            let opponents = record.seats.iter().filter(|b| b.entrant != a.entrant).count();
+            for b in record.seats.iter().filter(|b| b.entrant != a.entrant) {
+                let expected = 1.0 / (1.0 + 10f64.powf((ratings[b.entrant] - ratings[a.entrant]) / 400.0));
+                let score = match a.placement.cmp(&b.placement) {
+                    std::cmp::Ordering::Less => 1.0,
+                    std::cmp::Ordering::Equal => 0.5,
+                    std::cmp::Ordering::Greater => 0.0,
+                };
+                changes[a.entrant] += K_FACTOR * (score - expected) / opponents as f64;
+            }
+        }
+        for (rating, change) in ratings.iter_mut().zip(changes) {
+            *rating += change;
+        }
+    }
+
+    let mut kill_graph = vec![vec![0; count]; count];
+    for record in &matches {
+        for kill in &record.kills {
+            if let Some(killer) = kill.killer {
+                kill_graph[record.seats[killer].entrant][record.seats[kill.victim].entrant] += 1;
+            }
+        }
+    }
+
+    let samples = matches.iter().flat_map(|record| record.seats.iter().map(|seat| seat.masses.len())).max().unwrap_or(0);
+    let entrants = names.into_iter().enumerate().map(|(i, name)| {
+        let (seats, records): (Vec<_>, Vec<_>) = matches.iter()
+            .flat_map(|record| record.seats.iter().filter(|seat| seat.entrant == i).map(move |seat| (seat, record)))
+            .unzip();
+        let mean = |total: f64| if seats.is_empty() { 0.0 } else { total / seats.len() as f64 };
+        Standing {
+            name,
+            rating: ratings[i],
+            seats: seats.len(),
+            wins: seats.iter().filter(|seat| seat.placement == 1).count(),
+            mean_placement: mean(seats.iter().map(|seat| seat.placement as f64).sum()),
+            mean_survival: mean(seats.iter().zip(&records).map(|(seat, record)| seat.eliminated.unwrap_or(record.duration)).sum()),
+            mean_peak_mass: mean(seats.iter().map(|seat| seat.peak_mass).sum()),
+            kills: kill_graph[i].iter().sum(),
+            deaths: seats.iter().filter(|seat| seat.eliminated.is_some()).count(),
+            mass_curve: (0..samples).map(|sample| mean(seats.iter().map(|seat| seat.masses.get(sample).copied().unwrap_or(0.0)).sum())).collect(),
+        }
+    }).collect::<Vec<Standing>>();
+
+    let mut leaderboard = (0..count).collect::<Vec<_>>();
+    leaderboard.sort_by(|a, b| entrants[*b].rating.partial_cmp(&entrants[*a].rating).unwrap_or(std::cmp::Ordering::Equal));
+
+    Report {
+        seed: options.seed,
+        difficulty: options.difficulty.get_name().to_string(),
+        interval: options.interval,
+        entrants,
+        leaderboard,
+        kill_graph,
+        matches,
+    }
+}
+
+fn to_markdown(report: &Report) -> String {
+    let mut text = format!(
+        "# Tournament\n\n{} matches on {} difficulty, starting from seed {}.\n\n",
+        report.matches.len(),
+        report.difficulty,
+        report.seed,
+    );
+
+    text += "## Leaderboard\n\n";
+    text += "| Rank | Profile | Rating | Seats | Wins | Placement | Survival | Peak Mass | Kills | Deaths |\n";
+    text += "|-----:|:--------|-------:|------:|-----:|----------:|---------:|----------:|------:|-------:|\n";
+    for (rank, i) in report.leaderboard.iter().enumerate() {
+        let standing = &report.entrants[*i];
+        text += &format!(
+            "| {} | {} | {:.0} | {} | {} | {:.2} | {:.1} s | {:.1} | {} | {} |\n",
+            rank + 1,
+            standing.name,
+            standing.rating,
+            standing.seats,
+            standing.wins,
+            standing.mean_placement,
+            standing.mean_survival,
+            standing.mean_peak_mass,
+            standing.kills,
+            standing.deaths,
+        );
+    }
+
+    // Rows are the killers, and columns are who they knocked out
+    text += "\n## Kills\n\n| Killer \\ Victim |";
+    for i in &report.leaderboard {
+        text += &format!(" {} |", report.entrants[*i].name);
+    }
+    text += "\n|:--|";
+    text += &"--:|".repeat(report.leaderboard.len());
+    for killer in &report.leaderboard {
+        text += &format!("\n| {} |", report.entrants[*killer].name);
+        for victim in &report.leaderboard {
+            text += &format!(" {} |", report.kill_graph[*killer][*victim]);
+        }
+    }
+
+    text += "\n\n## Mass\n\nAverage mass, in multiples of the default mass, over time.\n\n| Profile |";
+    let samples = report.entrants.iter().map(|standing| standing.mass_curve.len()).max().unwrap_or(0);
+    for sample in 0..samples {
+        text += &format!(" {:.0} s |", sample as f64 * report.interval);
+    }
+    text += "\n|:--|";
+    text += &"--:|".repeat(samples);
+    for i in &report.leaderboard {
+        let standing = &report.entrants[*i];
+        text += &format!("\n| {} |", standing.name);
+        for mass in &standing.mass_curve {
+            text += &format!(" {:.0} |", mass);
+        }
+    }
+    text.push('\n');
+    text
+}
@@ -0,0 +1,180 @@
+// Tunes an AI profile by evolving it: every generation, each profile plays the same seeded
+// headless matches against the built-in bots, and the ones that live longest and grow
+// biggest are bred together to make the next generation.
+//
+//     $ cargo run --release --bin train -- --generations 30 --output trained.json
+//     $ cargo run --release -- --profile trained.json
+
+use microbiome::*;
+use microbiome::ai::{Difficulty, Profile};
+use microbiome::cli::{self, Args};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+
+// How many of the best profiles go on to the next generation unchanged
+const ELITE: usize = 2;
+
+// How many profiles are picked at random to compete for each parent slot
+const TOURNAMENT_SIZE: usize = 3;
+
+// How likely each gene is to change when a profile is bred, and by how much at most,
+// as a fraction of its value
+const MUTATION_RATE: f64 = 0.2;
+const MUTATION_SCALE: f64 = 0.3;
+
+// Settings picked on the command line
+#[derive(Clone, Debug)]
+struct Options {
+    generations: usize,
+    population: usize,
+    // How many matches each profile plays per generation
+    matches: usize,
+    // How many bots each profile plays against
+    opponents: usize,
+    // The longest a match goes on, in seconds of game time
+    duration: f64,
+    seed: u64,
+    difficulty: Difficulty,
+    // How much growing counts next to staying alive
+    mass_weight: f64,
+    // The scenario to train on instead of the default one
+    scenario: Option<Scenario>,
+    output: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            generations: 20,
+            population: 24,
+            matches: 3,
+            opponents: 9,
+            duration: 120.0,
+            seed: 0,
+            difficulty: Difficulty::Normal,
+            mass_weight: 0.25,
+            scenario: None,
+            output: "trained.json".to_string(),
+        }
+    }
+}
+
+impl Options {
+    fn from_args(args: impl Iterator<Item = String>) -> Options {
+        let mut options = Options::default();
+        let mut args = Args::new(args);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--generations" => options.generations = args.parse(&arg, "a number of generations"),
+                "--population" => options.population = args.parse::<usize>(&arg, "a number of profiles").max(ELITE + 1),
+                "--matches" => options.matches = args.parse::<usize>(&arg, "a number of matches").max(1),
+                "--opponents" => options.opponents = args.parse(&arg, "a number of bots"),
+                "--duration" => options.duration = args.parse(&arg, "a number of seconds"),
+                "--seed" => options.seed = args.parse(&arg, "a number"),
+                "--mass-weight" => options.mass_weight = args.parse(&arg, "a number"),
+                "--difficulty" => options.difficulty = args.difficulty(&arg),
+                "--scenario" => options.scenario = Some(args.scenario(&arg)),
+                "--output" => options.output = args.value(&arg, "a path to write the profile to"),
+                _ => eprintln!("Ignoring unknown argument {:?}", arg),
+            }
+        }
+        options
+    }
+}
+
+fn main() {
+    let options = Options::from_args(std::env::args().skip(1));
+    let scenario = options.scenario.clone().unwrap_or_else(|| {
+        Scenario::from_spawn_rules(&SpawnRules { bots: options.opponents, ..SpawnRules::default() })
+    });
+    let mut rng = StdRng::seed_from_u64(options.seed);
+
+    // Start from the built-in profiles, and fill up the rest with variations on them
+    let builtin = Profile::builtin().iter().map(Profile::get_genes).collect::<Vec<_>>();
+    let mut population = builtin.iter().cycle().take(options.population).cloned().collect::<Vec<_>>();
+    for genes in population.iter_mut().skip(builtin.len()) {
+        mutate(genes, &mut rng);
+    }
+
+    let mut best = (f64::NEG_INFINITY, population[0].clone());
+    for generation in 0..options.generations {
+        // Everyone plays the same matches, so that they're judged fairly
+        let seeds = (0..options.matches).map(|i| options.seed.wrapping_add((generation * options.matches + i) as u64)).collect::<Vec<_>>();
+        let scores = (0..population.len() * seeds.len()).into_par_iter()
+            .map(|i| evaluate(&to_profile(&population[i / seeds.len()]), &scenario, seeds[i % seeds.len()], &options))
+            .collect::<Vec<_>>();
+        let fitness = scores.chunks(seeds.len()).map(|scores| scores.iter().sum::<f64>() / scores.len() as f64).collect::<Vec<_>>();
+
+        let mut ranked = (0..population.len()).collect::<Vec<_>>();
+        ranked.sort_by(|a, b| fitness[*b].partial_cmp(&fitness[*a]).unwrap_or(std::cmp::Ordering::Equal));
+        let mean = fitness.iter().sum::<f64>() / fitness.len() as f64;
+        println!("Generation {}: best {:.3}, mean {:.3}", generation + 1, fitness[ranked[0]], mean);
+        if fitness[ranked[0]] > best.0 {
+            best = (fitness[ranked[0]], population[ranked[0]].clone());
+        }
+
+        // The best go through as they are, and everyone else is bred from winners of small tournaments
+        let mut next = ranked.iter().take(ELITE).map(|i| population[*i].clone()).collect::<Vec<_>>();
+        while next.len() < population.len() {
+            let mother = &population[tournament(&fitness, &mut rng)];
+            let father = &population[tournament(&fitness, &mut rng)];
+            let mut child = crossover(mother, father, &mut rng);
+            mutate(&mut child, &mut rng);
+            next.push(child);
+        }
+        population = next;
+    }
+
+    let profile = to_profile(&best.1);
+    println!("Best fitness: {:.3}", best.0);
+    println!("{}", profile.to_json());
+    if let Err(err) = std::fs::write(&options.output, profile.to_json()) {
+        cli::exit(format!("{}: {}", options.output, err));
+    }
+    println!("Saved to {}", options.output);
+}
+
+fn to_profile(genes: &[f64]) -> Profile {
+    Profile { name: "Trained".to_string(), ..Profile::default() }.with_genes(genes)
+}
+
+// Play a match with the profile as the first player, against whichever built-in bots the seed picks,
+// and score it by how much of the match it lived through and how much it grew.
+// Winning counts the same as lasting the whole match.
+fn evaluate(profile: &Profile, scenario: &Scenario, seed: u64, options: &Options) -> f64 {
+    let mut game = Match::new(scenario, options.difficulty, std::slice::from_ref(profile), seed);
+    let player = game.get_roster()[0];
+    let starting_mass = game.world.get_player_mass(&player).0;
+    while game.get_elapsed() < options.duration && game.is_alive(&player) && !game.is_over() {
+        game.step();
+    }
+
+    // Anyone still going at the end lived through the whole match, even if they won it early
+    let stats = game.world.get_stats(&player);
+    let survival = if game.is_alive(&player) { 1.0 } else { stats.time_alive / options.duration };
+    let growth = (stats.peak_mass.0 / starting_mass).ln().max(0.0);
+    survival + options.mass_weight * growth
+}
+
+// Pick the fittest of a few profiles chosen at random
+fn tournament(fitness: &[f64], rng: &mut StdRng) -> usize {
+    (0..TOURNAMENT_SIZE)
+        .map(|_| rng.gen_range(0..fitness.len()))
+        .max_by(|a, b| fitness[*a].partial_cmp(&fitness[*b]).unwrap_or(std::cmp::Ordering::Equal))
+        .unwrap()
+}
+
+// Take each gene from one parent or the other
+fn crossover(mother: &[f64], father: &[f64], rng: &mut StdRng) -> Vec<f64> {
+    mother.iter().zip(father).map(|(a, b)| if rng.gen_bool(0.5) { *a } else { *b }).collect()
+}
+
+// Nudge some of the genes up or down. Genes at zero get a small push, so that they can come back.
+fn mutate(genes: &mut [f64], rng: &mut StdRng) {
+    for gene in genes.iter_mut() {
+        if rng.gen_bool(MUTATION_RATE) {
+            *gene = (*gene * (1.0 + rng.gen_range(-MUTATION_SCALE..MUTATION_SCALE)) + rng.gen_range(-MUTATION_SCALE..MUTATION_SCALE) * 0.5).max(0.0);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use microbiome::*;
+use crate::render::View;
+
+// How much of the world is visible across the longer side of the window,
+// for a player with the default mass
+const CAMERA_SIZE: f64 = 1.0 / 8.0;
+
+// How quickly the camera catches up to where it should be, per second
+const CAMERA_EASING: f64 = 4.0;
+
+// How much room to leave around the player's cells, as a multiple of how far they're spread out
+const SPREAD_MARGIN: f64 = 2.5;
+
+// How far the mouse wheel can zoom in or out from the automatic zoom
+const MIN_ZOOM: f64 = 0.25;
+const MAX_ZOOM: f64 = 4.0;
+
+// How much of the visible world the free camera pans across per second
+const PAN_SPEED: f64 = 0.5;
+
+// Follows the player around, smoothly panning and zooming instead of jumping
+pub struct Camera {
+    // The center of the screen, in world coordinates
+    position: Position,
+    // Where the camera is heading
+    target: Position,
+    // How much of the world is visible across the longer side of the window
+    extent: f64,
+    // How much of the world should be visible before the mouse wheel is taken into account
+    target_extent: f64,
+    // The mouse wheel zooms relative to the automatic zoom
+    zoom: f64,
+    // Whether the camera has stopped following the player
+    free: bool,
+    // The camera jumps straight to the player the first time it follows them
+    settled: bool,
+}
+
+impl Camera {
+    pub fn new() -> Camera {
+        Camera {
+            position: Position(0.0, 0.0),
+            target: Position(0.0, 0.0),
+            extent: 2.0 * CAMERA_SIZE,
+            target_extent: 2.0 * CAMERA_SIZE,
+            zoom: 1.0,
+            free: false,
+            settled: false,
+        }
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.free
+    }
+
+    pub fn set_free(&mut self, free: bool) {
+        self.free = free;
+    }
+
+    // Zoom in (positive) or out (negative) with the mouse wheel
+    pub fn zoom_by(&mut self, amount: f64) {
+        self.zoom = (self.zoom * 1.1_f64.powf(-amount)).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    // Move the free camera across the screen, where (x, y) is a direction on the screen
+    pub fn pan(&mut self, x: f64, y: f64, seconds: f64) {
+        let distance = self.extent * PAN_SPEED * seconds;
+        self.position = self.position + Position(x * distance, y * distance);
+        self.target = self.position;
+    }
+
+    // Point the camera at a player's cells, zooming out as they grow and spread out
+    pub fn follow(&mut self, world: &World, player: &Player) {
+        let cells = world.get_player_cells(player);
+        if cells.is_empty() {
+            return;
+        }
+
+        let center = world.get_camera_position(player);
+        let total_mass = cells.iter().map(|cell| cell.get_mass().to_area()).sum::<f64>();
+        let spread = cells.iter()
+            .map(|cell| cell.get_position().distance_to(center) + cell.get_radius())
+            .fold(0.0, f64::max);
+
+        self.target_extent = (2.0 * CAMERA_SIZE * (total_mass / Mass::default().to_area()).powf(0.25)).max(spread * SPREAD_MARGIN);
+        self.target = center;
+        if !self.settled {
+            // Don't sweep across the world to find the player at the start of a match
+            self.position = center;
+            self.extent = self.target_extent * self.zoom;
+            self.settled = true;
+        }
+    }
+
+    // Ease the camera towards where it should be
+    pub fn update(&mut self, seconds: f64) {
+        let t = 1.0 - (-CAMERA_EASING * seconds).exp();
+        self.position = self.position + (self.target - self.position) * t;
+        // Zooming is eased in log space, so that zooming in and out feel the same
+        let extent = self.target_extent * self.zoom;
+        self.extent = (self.extent.ln() + (extent.ln() - self.extent.ln()) * t).exp();
+    }
+
+    // Get how the world should be laid out on a screen of this size
+    pub fn get_view(&self, screen_width: f64, screen_height: f64) -> View {
+        let world_size = 2.0 * screen_width.max(screen_height) / self.extent;
+        View::new(self.position, world_size, screen_width, screen_height)
+    }
+}
@@ -0,0 +1,81 @@
+use crate::*;
+use crate::ai::{Difficulty, Profile};
+use crate::script::{Scripts, SCRIPT_DIRECTORY};
+use std::fmt::Display;
+use std::str::FromStr;
+
+// Reads the command line for the game and its tools. Anything missing or wrong is reported,
+// and the program stops, since there's nothing sensible to carry on with instead.
+pub struct Args<I: Iterator<Item = String>> {
+    args: I,
+}
+
+impl<I: Iterator<Item = String>> Args<I> {
+    pub fn new(args: I) -> Args<I> {
+        Args { args }
+    }
+
+    // Get the value that goes with an argument, such as the path after `--scenario`
+    pub fn value(&mut self, arg: &str, what: &str) -> String {
+        self.args.next().unwrap_or_else(|| exit(format!("{} needs {}", arg, what)))
+    }
+
+    pub fn parse<T: FromStr>(&mut self, arg: &str, what: &str) -> T {
+        parse(&self.value(arg, what))
+    }
+
+    pub fn profile(&mut self, arg: &str) -> Profile {
+        let name = self.value(arg, "the name of a built-in profile, or a path to a profile file");
+        Profile::load(&name).unwrap_or_else(|err| exit(format!("{}: {}", name, err)))
+    }
+
+    pub fn difficulty(&mut self, arg: &str) -> Difficulty {
+        let name = self.value(arg, "easy, normal or hard");
+        Difficulty::from_name(&name).unwrap_or_else(|| exit(format!("Unknown difficulty {:?}", name)))
+    }
+
+    pub fn scenario(&mut self, arg: &str) -> Scenario {
+        let path = self.value(arg, "a path to a scenario file");
+        Scenario::load(&path).unwrap_or_else(|err| exit(format!("{}: {}", path, err)))
+    }
+
+    pub fn scripts(&mut self, arg: &str) -> Scripts {
+        load_scripts(&self.value(arg, "a path to a directory of scripts"))
+    }
+}
+
+impl<I: Iterator<Item = String>> Iterator for Args<I> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.args.next()
+    }
+}
+
+pub fn parse<T: FromStr>(value: &str) -> T {
+    value.parse().unwrap_or_else(|_| exit(format!("Couldn't understand {:?}", value)))
+}
+
+// Load every script in a directory. Scripts that can't be loaded are reported, and the rest are still used.
+pub fn load_scripts(directory: &str) -> Scripts {
+    let (scripts, errors) = Scripts::load(directory);
+    for error in errors {
+        eprintln!("{}", error);
+    }
+    scripts
+}
+
+// Make sure a script that was asked for by name is loaded, looking in the default directory
+// if no other directory was given
+pub fn require_script(scripts: &mut Option<Scripts>, name: &str) {
+    let scripts = scripts.get_or_insert_with(|| load_scripts(SCRIPT_DIRECTORY));
+    if !scripts.contains(name) {
+        exit(format!("No script named {:?} was loaded", name));
+    }
+}
+
+// Report a problem with the command line, and stop
+pub fn exit(message: impl Display) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1)
+}
@@ -0,0 +1,259 @@
+use crate::*;
+use crate::ai::{Decision, Difficulty, Order, Perception, Profile, Snapshot};
+use serde_derive::{Serialize, Deserialize};
+
+// The reward for the step an agent loses its last cell on
+const DEATH_PENALTY: f64 = -1.0;
+
+// How many numbers describe each entity in a `Encoding::Nearest` observation:
+// whether it's there, its offset and velocity, radius, mass, and whether it's food, the agent's own cell,
+// a teammate's cell or an enemy's cell
+const ENTITY_FEATURES: usize = 11;
+
+// How an agent's surroundings are turned into numbers
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    // A square picture centered on the agent, `size` pixels and `extent` world units across, with one
+    // layer each for food, the agent's own cells and enemy cells. Each pixel holds the mass inside it.
+    Raster { size: usize, extent: f64 },
+    // The `count` closest things the agent can see, closest first, with `ENTITY_FEATURES` numbers for each
+    Nearest { count: usize },
+}
+
+impl Encoding {
+    // Get the shape of one agent's observation
+    pub fn get_shape(&self) -> Vec<usize> {
+        match *self {
+            Encoding::Raster { size, .. } => vec![3, size, size],
+            Encoding::Nearest { count } => vec![count, ENTITY_FEATURES],
+        }
+    }
+}
+
+// How an environment sets up its matches
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvironmentConfig {
+    pub scenario: Scenario,
+    // How many of the scenario's players are controlled by agents. The agents take the seats meant
+    // for humans first, and then the other players' seats in order. Everyone else is played by the built-in bots.
+    pub agents: usize,
+    pub difficulty: Difficulty,
+    pub encoding: Encoding,
+    // How many ticks of `TIME_STEP` seconds each step plays, with the agents' actions held the whole time
+    pub ticks_per_step: usize,
+    // How many seconds of game time a match can go on for
+    pub max_seconds: f64,
+    // The fastest an agent can steer, as a multiple of the default speed
+    pub max_speed: f64,
+}
+
+impl Default for EnvironmentConfig {
+    fn default() -> Self {
+        EnvironmentConfig {
+            scenario: Scenario::from_spawn_rules(&SpawnRules::default()),
+            agents: 1,
+            difficulty: Difficulty::default(),
+            encoding: Encoding::Nearest { count: 32 },
+            ticks_per_step: 1,
+            max_seconds: 300.0,
+            max_speed: Profile::default().speed,
+        }
+    }
+}
+
+// What every agent can see after a step
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    // One list of numbers for each agent, laid out as `shape` describes, or all zeros for agents that are out
+    pub agents: Vec<Vec<f64>>,
+    pub shape: Vec<usize>,
+}
+
+// A match for training learned agents against, in the style of a Gym environment: `reset` starts
+// a new match, and `step` plays the agents' actions and says how well they did. Everything runs
+// without a window, and the same seed and actions always play out the same way.
+pub struct Environment {
+    config: EnvironmentConfig,
+    game: Option<Match>,
+    agents: Vec<Player>,
+    // The agents' total mass after the last step, or None while they have no cells
+    masses: Vec<Option<f64>>,
+}
+
+impl Environment {
+    pub fn new(config: EnvironmentConfig) -> Environment {
+        Environment {
+            config,
+            game: None,
+            agents: Vec::new(),
+            masses: Vec::new(),
+        }
+    }
+
+    pub fn get_config(&self) -> &EnvironmentConfig {
+        &self.config
+    }
+
+    // Get the world of the current match, if one has been started
+    pub fn get_world(&self) -> Option<&World> {
+        self.game.as_ref().map(|game| &game.world)
+    }
+
+    // Get the players the agents control
+    pub fn get_agents(&self) -> &[Player] {
+        &self.agents
+    }
+
+    // Start a new match
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let mut game = Match::new(&self.config.scenario, self.config.difficulty, &[], seed);
+
+        // The humans' seats come after everyone else's in the roster
+        let humans = self.config.scenario.players.iter().filter(|spec| spec.human).map(|spec| spec.count).sum::<usize>();
+        let roster = game.get_roster();
+        let (others, humans) = roster.split_at(roster.len().saturating_sub(humans));
+        self.agents = humans.iter().chain(others).take(self.config.agents).copied().collect();
+        for agent in &self.agents {
+            game.bots.remove(agent);
+        }
+        self.masses = self.agents.iter().map(|agent| Some(game.world.get_player_mass(agent).to_area())).collect();
+        self.game = Some(game);
+        self.observe()
+    }
+
+    // Carry out one action for each agent, in the same order as `get_agents`, and get what they see now,
+    // each agent's reward, and whether the match is done. The reward is the log of how much the agent's
+    // mass grew, or `DEATH_PENALTY` on the step it's knocked out, and nothing while it waits to respawn.
+    // Agents without an action keep going.
+    pub fn step(&mut self, actions: &[Order]) -> (Observation, Vec<f64>, bool) {
+        let game = self.game.as_mut().expect("the environment needs to be reset before it can step");
+        let profile = Profile { speed: self.config.max_speed, ..Profile::default() };
+        let decisions = self.agents.iter().zip(actions).map(|(agent, order)| order.to_decision(agent, &profile)).collect::<Vec<_>>();
+
+        // Only split once, even when the step is several ticks long
+        game.step_with(&decisions);
+        let held = decisions.into_iter().map(|decision| Decision { split: false, ..decision }).collect::<Vec<_>>();
+        for _ in 1..self.config.ticks_per_step {
+            game.step_with(&held);
+        }
+
+        let rewards = self.agents.iter().zip(self.masses.iter_mut()).map(|(agent, mass)| {
+            let before = *mass;
+            *mass = Some(game.world.get_player_mass(agent).to_area()).filter(|mass| *mass > 0.0);
+            match (before, *mass) {
+                (Some(before), Some(after)) => (after / before).ln(),
+                (Some(_), None) => DEATH_PENALTY,
+                // Out, or only just back after respawning
+                (None, _) => 0.0,
+            }
+        }).collect();
+
+        let done = game.is_over() || game.get_elapsed() >= self.config.max_seconds || self.agents.iter().all(|agent| !game.is_alive(agent));
+        (self.observe(), rewards, done)
+    }
+
+    fn observe(&self) -> Observation {
+        let shape = self.config.encoding.get_shape();
+        let length = shape.iter().product();
+        let Some(game) = &self.game else {
+            return Observation { agents: Vec::new(), shape };
+        };
+
+        let snapshot = Snapshot::new(&game.world);
+        let agents = self.agents.iter().map(|agent| {
+            Perception::new(&snapshot, agent, 1.0).map_or_else(|| vec![0.0; length], |perception| encode(&perception, self.config.encoding))
+        }).collect();
+        Observation { agents, shape }
+    }
+}
+
+// Turn what an agent can see into numbers. Masses are relative to the agent's biggest cell,
+// so anything under 1 is something the agent could eat.
+fn encode(perception: &Perception, encoding: Encoding) -> Vec<f64> {
+    let center = perception.center;
+    let biggest = perception.cells.iter().map(|cell| cell.get_mass().to_area()).fold(0.0, f64::max);
+    let player = &perception.player;
+
+    // Everything the agent can see, as its position, velocity, radius, mass, and which layer or kind it is
+    let food = perception.food.iter().map(|food| (food.get_position(), (0.0, 0.0), food.to_radius(), food.to_mass(), 0))
+        .chain(perception.patches.iter().map(|patch| (patch.position, (0.0, 0.0), patch.mass.to_radius(), patch.mass, 0)));
+    let cell = |cell: &Cell, kind: usize| (cell.get_position(), cell.get_mass().calculate_slowness(cell.get_speed()).to_vector(&cell.get_direction()), cell.get_radius(), cell.get_mass(), kind);
+    let own = perception.cells.iter().map(|own| cell(own, 1));
+    let others = perception.others.iter().map(|(_, other)| {
+        let ally = other.get_player().is_some_and(|owner| owner.is_ally_of(player));
+        cell(other, if ally { 2 } else { 3 })
+    });
+    let entities = food.chain(own).chain(others);
+
+    match encoding {
+        Encoding::Raster { size, extent } => {
+            let mut pixels = vec![0.0; 3 * size * size];
+            for (position, _, _, mass, kind) in entities {
+                // Teammates' cells don't have a layer of their own
+                let layer = match kind {
+                    0 | 1 => kind,
+                    3 => 2,
+                    _ => continue,
+                };
+                let Position(x, y) = (position - center) * (1.0 / extent) + Position(0.5, 0.5);
+                if (0.0..1.0).contains(&x) && (0.0..1.0).contains(&y) {
+                    let (column, row) = ((x * size as f64) as usize, (y * size as f64) as usize);
+                    pixels[(layer * size + row) * size + column] += mass.to_area() / biggest;
+                }
+            }
+            pixels
+        }
+        Encoding::Nearest { count } => {
+            let mut entities = entities.collect::<Vec<_>>();
+            entities.sort_by(|a, b| a.0.distance_to(center).partial_cmp(&b.0.distance_to(center)).unwrap_or(std::cmp::Ordering::Equal));
+            let mut features = vec![0.0; count * ENTITY_FEATURES];
+            for (slot, (position, (vx, vy), radius, mass, kind)) in features.chunks_mut(ENTITY_FEATURES).zip(entities) {
+                let Position(dx, dy) = position - center;
+                slot[..7].copy_from_slice(&[1.0, dx, dy, vx, vy, radius, mass.to_area() / biggest]);
+                slot[7 + kind] = 1.0;
+            }
+            features
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> EnvironmentConfig {
+        EnvironmentConfig {
+            scenario: Scenario::from_spawn_rules(&SpawnRules { bots: 3, starting_cells: 2, ..SpawnRules::default() }),
+            agents: 2,
+            encoding: Encoding::Nearest { count: 8 },
+            ..EnvironmentConfig::default()
+        }
+    }
+
+    #[test]
+    fn same_seed_and_orders_play_out_the_same() {
+        let orders = [
+            Order { direction: Direction::from_radians(0.5), speed: 2.0, split: true },
+            Order { direction: Direction::from_radians(-2.0), speed: 1.0, split: false },
+        ];
+        let play = || {
+            let mut environment = Environment::new(config());
+            let first = environment.reset(7);
+            let steps = (0..2).map(|_| environment.step(&orders)).collect::<Vec<_>>();
+            (first, steps)
+        };
+        let (first, steps) = play();
+        assert_eq!(first.agents.len(), 2);
+        assert_eq!((first, steps), play());
+    }
+
+    #[test]
+    fn agents_take_the_human_seat_first() {
+        let mut environment = Environment::new(config());
+        environment.reset(7);
+        let names = environment.get_agents().iter().map(Player::get_name).collect::<Vec<_>>();
+        assert_eq!(names, ["Player", "Bot"]);
+    }
+}
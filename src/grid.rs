@@ -0,0 +1,109 @@
+use crate::*;
+
+// Sorts things into square buckets by where they are in the arena, so that finding
+// everything near a position only means looking through the buckets around it
+#[derive(Clone, Debug)]
+pub struct Grid<T> {
+    // Half the width of the arena, the same as the world's bounds
+    bounds: f64,
+    // The width of each bucket
+    size: f64,
+    columns: usize,
+    buckets: Vec<Vec<(Position, T)>>,
+}
+
+impl<T> Grid<T> {
+    // Make an empty grid over an arena with the given bounds, with this many buckets along each side
+    pub fn new(bounds: f64, columns: usize) -> Grid<T> {
+        let columns = columns.max(1);
+        Grid {
+            bounds,
+            size: 2.0 * bounds / columns as f64,
+            columns,
+            buckets: (0..columns * columns).map(|_| Vec::new()).collect(),
+        }
+    }
+
+    pub fn insert(&mut self, position: Position, item: T) {
+        let bucket = self.get_bucket(position);
+        self.buckets[bucket].push((position, item));
+    }
+
+    // Get everything within a radius of a position
+    pub fn query(&self, position: Position, radius: f64) -> impl Iterator<Item = &(Position, T)> {
+        self.get_buckets(position, radius)
+            .flat_map(|(_, items)| items.iter())
+            .filter(move |(other, _)| other.distance_to(position) <= radius)
+    }
+
+    // Get the index of every bucket that could have something within a radius of a position,
+    // along with what's in it
+    pub fn get_buckets(&self, position: Position, radius: f64) -> impl Iterator<Item = (usize, &[(Position, T)])> {
+        let (min_column, max_column) = (self.get_column(position.get_x() - radius), self.get_column(position.get_x() + radius));
+        let (min_row, max_row) = (self.get_column(position.get_y() - radius), self.get_column(position.get_y() + radius));
+        (min_row..=max_row)
+            .flat_map(move |row| (min_column..=max_column).map(move |column| row * self.columns + column))
+            .map(move |bucket| (bucket, self.buckets[bucket].as_slice()))
+    }
+
+    // Get the index of the bucket a position falls in
+    pub fn get_bucket(&self, position: Position) -> usize {
+        self.get_column(position.get_y()) * self.columns + self.get_column(position.get_x())
+    }
+
+    pub fn get_bucket_count(&self) -> usize {
+        self.buckets.len()
+    }
+
+    // Get the width of each bucket
+    pub fn get_bucket_size(&self) -> f64 {
+        self.size
+    }
+
+    pub fn len(&self) -> usize {
+        self.buckets.iter().map(|bucket| bucket.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.iter().all(|bucket| bucket.is_empty())
+    }
+
+    // Get which column (or row) a coordinate falls in. Anything outside the arena goes in the closest one.
+    fn get_column(&self, coordinate: f64) -> usize {
+        (((coordinate + self.bounds) / self.size).max(0.0) as usize).min(self.columns - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_at_the_edge_of_the_arena() {
+        let mut grid = Grid::new(1.0, 4);
+        grid.insert(Position(1.0, 1.0), "corner");
+        grid.insert(Position(-1.0, -1.0), "opposite corner");
+        // Anything outside the arena goes in the closest bucket, so it can still be found
+        grid.insert(Position(1.5, 0.0), "outside");
+
+        let found = grid.query(Position(1.0, 1.0), 0.1).map(|(_, item)| *item).collect::<Vec<_>>();
+        assert_eq!(found, vec!["corner"]);
+        let found = grid.query(Position(0.95, 0.0), 0.6).map(|(_, item)| *item).collect::<Vec<_>>();
+        assert_eq!(found, vec!["outside"]);
+        assert_eq!(grid.get_bucket(Position(5.0, 5.0)), grid.get_bucket_count() - 1);
+        assert_eq!(grid.get_bucket(Position(-5.0, -5.0)), 0);
+    }
+
+    #[test]
+    fn query_leaves_out_things_past_the_radius() {
+        let mut grid = Grid::new(1.0, 2);
+        grid.insert(Position(0.1, 0.1), 1);
+        grid.insert(Position(0.9, 0.9), 2);
+        assert_eq!(grid.len(), 2);
+
+        // Both are in the same bucket, but only one is close enough
+        let found = grid.query(Position(0.0, 0.0), 0.5).map(|(_, item)| *item).collect::<Vec<_>>();
+        assert_eq!(found, vec![1]);
+        assert!(grid.query(Position(-0.5, -0.5), 0.1).next().is_none());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::*;
+use crate::ai::{self, Bots, Decision, Difficulty, Profile};
+
+// How many seconds of game time pass on each tick of a headless match
+pub const TIME_STEP: f64 = 1.0 / 30.0;
+
+// A match played out by bots without a window, which goes exactly the same way every time it's
+// played with the same seed. Every player in the scenario is a bot, including the human.
+pub struct Match {
+    pub world: World,
+    pub bots: Bots,
+    // Everyone who started the match, in the order they were handed profiles
+    roster: Vec<Player>,
+    seeded: Seeded,
+    elapsed: f64,
+}
+
+impl Match {
+    // Set up a match from a scenario. The players are handed the profiles in order; anyone left over
+    // gets the profile the scenario picked for them, or else a random one.
+    pub fn new(scenario: &Scenario, difficulty: Difficulty, profiles: &[Profile], seed: u64) -> Match {
+        let mut seeded = Seeded::new(seed);
+        let (world, bots, roster) = seeded.run(|| {
+            let (world, _, mut picked) = scenario.build();
+            let roster = world.get_players().into_iter().copied().collect::<Vec<_>>();
+            let mut bots = Bots::new(difficulty);
+            for (i, player) in roster.iter().enumerate() {
+                let profile = profiles.get(i).cloned()
+                    .or_else(|| picked.remove(&player.get_id()))
+                    .unwrap_or_else(Profile::random);
+                bots.add(player, profile);
+            }
+            (world, bots, roster)
+        });
+
+        Match {
+            world,
+            bots,
+            roster,
+            seeded,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn get_roster(&self) -> &[Player] {
+        &self.roster
+    }
+
+    // Get how many seconds of game time have been played
+    pub fn get_elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    // Check if a player still has cells, or is waiting to respawn
+    pub fn is_alive(&self, player: &Player) -> bool {
+        self.world.get_player(player.get_id()).is_some() || self.world.is_respawning(player)
+    }
+
+    // Check if there's only one side left standing
+    pub fn is_over(&self) -> bool {
+        self.world.get_players().first().is_none_or(|player| self.world.is_last_team_standing(player))
+    }
+
+    // Play a single tick, and get what the bots decided to do on it
+    pub fn step(&mut self) -> Vec<Decision> {
+        self.step_with(&[])
+    }
+
+    // Play a single tick, carrying out some decisions made outside the match along with
+    // the bots' own. Players given decisions here should be taken away from the bots first.
+    pub fn step_with(&mut self, decisions: &[Decision]) -> Vec<Decision> {
+        let Match { world, bots, seeded, .. } = self;
+        let decisions = seeded.run(|| {
+            let decisions = [decisions.to_vec(), bots.think(world, TIME_STEP)].concat();
+            ai::act(world, &decisions);
+            world.tick(TIME_STEP);
+            // Without a clock on the wall, respawns go by game time
+            world.tick_respawns(TIME_STEP);
+            decisions
+        });
+        self.elapsed += TIME_STEP;
+        decisions
+    }
+}
@@ -0,0 +1,261 @@
+use ggez::Context;
+use ggez::input::gamepad::gilrs::{Axis, Button};
+use ggez::input::keyboard::KeyCode;
+use serde_derive::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// How far an analog stick has to be pushed before it counts, from 0 to 1
+const DEADZONE: f64 = 0.15;
+
+// Something the player can do with a key or a gamepad button
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Split,
+    // Pull all of the player's cells together, so they merge sooner
+    Merge,
+    Pause,
+    // Free the camera to look around the dish
+    Spectate,
+    // Pick whatever is selected on a menu
+    Confirm,
+    // Open the settings from the main menu
+    Settings,
+    Fullscreen,
+    Debug,
+    Decisions,
+    Minimap,
+    MassLabels,
+    // Let the AI steer the player until it's pressed again
+    Autopilot,
+}
+
+// The keys and buttons bound to each action, as they're written in a controls file
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bindings {
+    pub keys: HashMap<Action, Vec<String>>,
+    pub buttons: HashMap<Action, Vec<String>>,
+    pub deadzone: Option<f64>,
+}
+
+#[derive(Debug)]
+pub enum ControlsError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    UnknownKey(String),
+    UnknownButton(String),
+}
+
+impl fmt::Display for ControlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ControlsError::Io(err) => write!(f, "could not read controls: {}", err),
+            ControlsError::Json(err) => write!(f, "could not parse controls: {}", err),
+            ControlsError::UnknownKey(name) => write!(f, "unknown key {:?}", name),
+            ControlsError::UnknownButton(name) => write!(f, "unknown gamepad button {:?}", name),
+        }
+    }
+}
+
+impl std::error::Error for ControlsError {}
+
+impl From<std::io::Error> for ControlsError {
+    fn from(err: std::io::Error) -> Self {
+        ControlsError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for ControlsError {
+    fn from(err: serde_json::Error) -> Self {
+        ControlsError::Json(err)
+    }
+}
+
+// Turns the keyboard and gamepads into actions and steering
+#[derive(Clone, Debug)]
+pub struct Controls {
+    keys: HashMap<Action, Vec<KeyCode>>,
+    buttons: HashMap<Action, Vec<Button>>,
+    deadzone: f64,
+    // The actions held down by a gamepad on the last update, to tell when they're first pressed
+    held: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        use Action::*;
+        let keys = [
+            (Up, vec![KeyCode::W, KeyCode::Up]),
+            (Down, vec![KeyCode::S, KeyCode::Down]),
+            (Left, vec![KeyCode::A, KeyCode::Left]),
+            (Right, vec![KeyCode::D, KeyCode::Right]),
+            (Split, vec![KeyCode::Space]),
+            (Merge, vec![KeyCode::Q]),
+            (Pause, vec![KeyCode::Escape, KeyCode::P]),
+            (Spectate, vec![KeyCode::C]),
+            (Confirm, vec![KeyCode::Return]),
+            (Settings, vec![KeyCode::Tab]),
+            (Fullscreen, vec![KeyCode::F11]),
+            (Debug, vec![KeyCode::F3]),
+            (Decisions, vec![KeyCode::F4]),
+            (Minimap, vec![KeyCode::M]),
+            (MassLabels, vec![KeyCode::L]),
+            (Autopilot, vec![KeyCode::T]),
+        ];
+        let buttons = [
+            (Up, vec![Button::DPadUp]),
+            (Down, vec![Button::DPadDown]),
+            (Left, vec![Button::DPadLeft]),
+            (Right, vec![Button::DPadRight]),
+            (Split, vec![Button::South, Button::RightTrigger]),
+            (Merge, vec![Button::West, Button::LeftTrigger]),
+            (Pause, vec![Button::Start]),
+            (Spectate, vec![Button::Select]),
+            (Confirm, vec![Button::South]),
+            (Settings, vec![Button::North]),
+            (Autopilot, vec![Button::RightThumb]),
+        ];
+        Controls {
+            keys: keys.into_iter().collect(),
+            buttons: buttons.into_iter().collect(),
+            deadzone: DEADZONE,
+            held: HashSet::new(),
+            just_pressed: HashSet::new(),
+        }
+    }
+}
+
+impl Controls {
+    // Load a controls file. Any action it doesn't mention keeps its default bindings.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Controls, ControlsError> {
+        let json = std::fs::read_to_string(path)?;
+        Controls::from_bindings(&serde_json::from_str(&json)?)
+    }
+
+    pub fn from_bindings(bindings: &Bindings) -> Result<Controls, ControlsError> {
+        let mut controls = Controls::default();
+        for (action, names) in &bindings.keys {
+            let keys = names.iter().map(|name| parse_key(name).ok_or_else(|| ControlsError::UnknownKey(name.clone())));
+            controls.keys.insert(*action, keys.collect::<Result<_, _>>()?);
+        }
+        for (action, names) in &bindings.buttons {
+            let buttons = names.iter().map(|name| parse_button(name).ok_or_else(|| ControlsError::UnknownButton(name.clone())));
+            controls.buttons.insert(*action, buttons.collect::<Result<_, _>>()?);
+        }
+        if let Some(deadzone) = bindings.deadzone {
+            controls.deadzone = deadzone.clamp(0.0, 1.0);
+        }
+        Ok(controls)
+    }
+
+    // Check which gamepad buttons were pressed since the last update. Call this once per frame.
+    pub fn update(&mut self, ctx: &Context) {
+        let held = self.buttons.iter()
+            .filter(|(_, buttons)| ctx.gamepad.gamepads().any(|(_, gamepad)| buttons.iter().any(|button| gamepad.is_pressed(*button))))
+            .map(|(action, _)| *action)
+            .collect::<HashSet<_>>();
+        self.just_pressed = held.difference(&self.held).copied().collect();
+        self.held = held;
+    }
+
+    pub fn is_pressed(&self, ctx: &Context, action: Action) -> bool {
+        self.held.contains(&action) || self.keys.get(&action).is_some_and(|keys| keys.iter().any(|key| ctx.keyboard.is_key_pressed(*key)))
+    }
+
+    pub fn is_just_pressed(&self, ctx: &Context, action: Action) -> bool {
+        self.just_pressed.contains(&action) || self.keys.get(&action).is_some_and(|keys| keys.iter().any(|key| ctx.keyboard.is_key_just_pressed(*key)))
+    }
+
+    // Get the direction the player is steering in with the keys or an analog stick, as (x, y)
+    // on the screen with a length of up to 1, or None when they're leaving it to the mouse
+    pub fn get_steering(&self, ctx: &Context) -> Option<(f64, f64)> {
+        let axis = |negative, positive| self.is_pressed(ctx, positive) as i32 as f64 - self.is_pressed(ctx, negative) as i32 as f64;
+        let (x, y) = (axis(Action::Left, Action::Right), axis(Action::Up, Action::Down));
+        if x != 0.0 || y != 0.0 {
+            let length = x.hypot(y);
+            return Some((x / length, y / length));
+        }
+
+        for (_, gamepad) in ctx.gamepad.gamepads() {
+            // Sticks point up for positive values, but the screen's y axis points down
+            let (x, y) = (gamepad.value(Axis::LeftStickX) as f64, -gamepad.value(Axis::LeftStickY) as f64);
+            let length = x.hypot(y);
+            if length > self.deadzone {
+                // Rescale so that the stick goes smoothly from 0 at the edge of the deadzone
+                let scale = ((length - self.deadzone) / (1.0 - self.deadzone)).min(1.0) / length;
+                return Some((x * scale, y * scale));
+            }
+        }
+        None
+    }
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    use KeyCode::*;
+    const LETTERS: [KeyCode; 26] = [A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z];
+    const DIGITS: [KeyCode; 10] = [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    const FUNCTIONS: [KeyCode; 12] = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphabetic() {
+            return Some(LETTERS[(c.to_ascii_uppercase() as u8 - b'A') as usize]);
+        }
+        if let Some(digit) = c.to_digit(10) {
+            return Some(DIGITS[digit as usize]);
+        }
+    }
+    if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<usize>().ok()) {
+        return FUNCTIONS.get(n.checked_sub(1)?).copied();
+    }
+
+    Some(match name {
+        "Up" => Up,
+        "Down" => Down,
+        "Left" => Left,
+        "Right" => Right,
+        "Space" => Space,
+        "Enter" | "Return" => Return,
+        "Escape" => Escape,
+        "Tab" => Tab,
+        "Backspace" => Back,
+        "LShift" => LShift,
+        "RShift" => RShift,
+        "LControl" => LControl,
+        "RControl" => RControl,
+        "LAlt" => LAlt,
+        "RAlt" => RAlt,
+        _ => return None,
+    })
+}
+
+fn parse_button(name: &str) -> Option<Button> {
+    use Button::*;
+    Some(match name {
+        "South" => South,
+        "East" => East,
+        "North" => North,
+        "West" => West,
+        "LeftTrigger" => LeftTrigger,
+        "LeftTrigger2" => LeftTrigger2,
+        "RightTrigger" => RightTrigger,
+        "RightTrigger2" => RightTrigger2,
+        "Select" => Select,
+        "Start" => Start,
+        "Mode" => Mode,
+        "LeftThumb" => LeftThumb,
+        "RightThumb" => RightThumb,
+        "DPadUp" => DPadUp,
+        "DPadDown" => DPadDown,
+        "DPadLeft" => DPadLeft,
+        "DPadRight" => DPadRight,
+        _ => return None,
+    })
+}
@@ -1,42 +1,279 @@
 use core::ops::{Neg, Add, Sub, Mul};
 use rayon::prelude::*;
 use serde_derive::{Serialize, Deserialize};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::BuildHasherDefault;
+
+pub mod ai;
+pub mod cli;
+pub mod script;
+mod mode;
+pub use mode::*;
+mod grid;
+pub use grid::*;
+mod headless;
+pub use headless::*;
+mod environment;
+pub use environment::*;
+mod scenario;
+pub use scenario::*;
+
+// How many random spots to try when looking for a place to spawn a cell
+const SPAWN_CANDIDATES: usize = 64;
+
+// The most new cells a single split can create
+pub const SPLIT_LIMIT: usize = 256;
+
+// How many seconds old a cell must be before it can split
+pub const SPLIT_AGE: f64 = 8.0;
+
+// A hash map that always goes through its entries in the same order for the same keys,
+// so that a seeded world plays out the same way every time
+pub type Map<K, V> = HashMap<K, V, BuildHasherDefault<DefaultHasher>>;
+
+// The random numbers and IDs for something that should play out the same way every time it's run
+#[derive(Clone, Debug)]
+pub struct Seeded {
+    rng: StdRng,
+    next_id: u32,
+}
+
+thread_local! {
+    // The random numbers and IDs this thread is using right now
+    static SEEDED: RefCell<Seeded> = RefCell::new(Seeded { rng: StdRng::from_entropy(), next_id: 0 });
+}
+
+impl Seeded {
+    pub fn new(seed: u64) -> Seeded {
+        Seeded {
+            rng: StdRng::seed_from_u64(seed),
+            next_id: 0,
+        }
+    }
+
+    // Run something with this seed's random numbers and IDs, picking up where the last run left off.
+    // Each thread has its own, and runs can be nested, so seeded matches can be played in parallel.
+    pub fn run<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        // Swap back on the way out, even if something panics
+        struct Restore<'a>(&'a mut Seeded);
+        impl Drop for Restore<'_> {
+            fn drop(&mut self) {
+                SEEDED.with(|seeded| std::mem::swap(&mut *seeded.borrow_mut(), self.0));
+            }
+        }
+
+        SEEDED.with(|seeded| std::mem::swap(&mut *seeded.borrow_mut(), self));
+        let _restore = Restore(self);
+        f()
+    }
+}
+
+// Use the random number generator for whatever this thread is running
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    SEEDED.with(|seeded| f(&mut seeded.borrow_mut().rng))
+}
 
 // Get random number from -1.0 to 1.0
 pub fn random() -> f64 {
-    rand::thread_rng().gen_range(-1.0..1.0)
+    with_rng(|rng| rng.gen_range(-1.0..1.0))
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct World {
     pub players: Vec<Player>,
-    pub entities: HashMap<ID, Entity>,
+    pub teams: Vec<Team>,
+    pub entities: Map<ID, Entity>,
     pub tick: u64,
+    // Half the width of the square arena, centered on the origin
+    pub bounds: f64,
+    pub config: WorldConfig,
+    // Eliminated players waiting to come back
+    pub respawns: Vec<Respawn>,
+    // Lifetime stats for every player, kept even after they're eliminated
+    pub stats: HashMap<ID, Stats>,
+    // Where each player that's gathering its cells is pulling them together this tick
+    #[serde(default)]
+    pub gathers: Map<ID, Position>,
 }
 
 impl World {
     pub fn new() -> World {
         World {
             players: Vec::new(),
-            entities: HashMap::new(),
+            teams: Vec::new(),
+            entities: Map::default(),
             tick: 0,
+            bounds: 1.0,
+            config: WorldConfig::default(),
+            respawns: Vec::new(),
+            stats: HashMap::new(),
+            gathers: Map::default(),
+        }
+    }
+
+    pub fn get_stats(&self, player: &Player) -> Stats {
+        self.stats.get(&player.get_id()).copied().unwrap_or_default()
+    }
+
+    pub fn get_stats_mut(&mut self, id: ID) -> &mut Stats {
+        self.stats.entry(id).or_default()
+    }
+
+    pub fn is_respawning(&self, player: &Player) -> bool {
+        self.get_respawn_time(player).is_some()
+    }
+
+    // Get the number of seconds until an eliminated player comes back
+    pub fn get_respawn_time(&self, player: &Player) -> Option<f64> {
+        self.respawns.iter().find(|respawn| respawn.player.get_id() == player.get_id()).map(|respawn| respawn.remaining.max(0.0))
+    }
+
+    // Find a good place for a new cell of the given mass to appear.
+    // This samples random spots inside the arena (and outside of any walls),
+    // and scores them by how far they are from every cell that could eat it.
+    pub fn find_spawn_position(&self, mass: Mass) -> Position {
+        let radius = mass.to_radius();
+        let threats = self.get_cells().into_iter()
+            .filter(|cell| cell.get_mass().to_area() > mass.to_area() * 1.1)
+            .map(|cell| (cell.get_position(), cell.get_radius()))
+            .collect::<Vec<_>>();
+        let walls = self.entities.values().filter_map(|entity| match entity {
+            Entity::Wall(wall) => Some(*wall),
+            _ => None,
+        }).collect::<Vec<_>>();
+
+        // Keep the whole cell inside the arena
+        let range = (self.bounds - radius).max(0.0);
+        let mut best = None;
+        for _ in 0..SPAWN_CANDIDATES {
+            let pos = Position(random() * range, random() * range);
+            if walls.iter().any(|wall| wall.get_position().distance_to(pos) < wall.get_radius() + radius) {
+                continue;
+            }
+
+            // The score is the distance to the edge of the closest threat
+            let score = threats.iter()
+                .map(|(threat_pos, threat_radius)| threat_pos.distance_to(pos) - threat_radius - radius)
+                .fold(f64::INFINITY, f64::min);
+            if score > self.config.safe_radius {
+                return pos;
+            }
+            if best.is_none_or(|(best_score, _)| score > best_score) {
+                best = Some((score, pos));
+            }
         }
+
+        best.map(|(_, pos)| pos).unwrap_or_else(|| self.random_position())
+    }
+
+    // Bring an eliminated player back with a fresh starting cell
+    pub fn respawn(&mut self, player: Player) {
+        let mass = self.config.respawn_mass;
+        let pos = self.find_spawn_position(mass);
+        let cell = Cell::new(mass, pos, Direction::from_radians(random() * std::f64::consts::PI), Speed::default(), Some(player));
+        self.players.push(player);
+        self.add_entity(Entity::Cell(cell));
+    }
+
+    // Get a random position inside the arena
+    pub fn random_position(&self) -> Position {
+        Position(random() * self.bounds, random() * self.bounds)
+    }
+
+    pub fn set_bounds(&mut self, bounds: f64) {
+        self.bounds = bounds;
+    }
+
+    pub fn get_bounds(&self) -> f64 {
+        self.bounds
     }
 
     pub fn create_new_player(&mut self, name: [char; 32], color: Color) -> Player {
-        let player = Player::new(name, ID::new(), color);
+        let player = self.create_empty_player(name, color);
+        // Create a new cell for the player
         let mut cell = Cell::default();
         cell.set_player(player);
-        // Add the player to the world
-        
-        self.players.push(player);
+        cell.set_position(self.find_spawn_position(cell.get_mass()));
         self.add_entity(Entity::Cell(cell));
-        // Create a new cell for the player
         player
     }
 
+    // Add a player to the world without giving them any cells.
+    // They'll be eliminated on the next tick unless they get some!
+    pub fn create_empty_player(&mut self, name: [char; 32], color: Color) -> Player {
+        let player = Player::new(name, ID::new(), color);
+        self.players.push(player);
+        player
+    }
+
+    pub fn create_new_team(&mut self, color: Color, friendly_fire: bool) -> Team {
+        let team = Team::new(ID::new(), color, friendly_fire);
+        self.teams.push(team);
+        team
+    }
+
+    pub fn get_teams(&self) -> Vec<&Team> {
+        self.teams.iter().collect()
+    }
+
+    // Move a player (and all of its cells) onto a team, or off of any team
+    pub fn set_team(&mut self, player: &Player, team: Option<Team>) -> Player {
+        let mut updated = *player;
+        updated.team = team;
+
+        if let Some(p) = self.players.iter_mut().find(|p| p.get_id() == player.get_id()) {
+            p.team = team;
+            updated = *p;
+        }
+
+        // Cells keep a copy of their owner, so they need to learn about the new team too
+        for cell in self.get_player_cells_mut(player) {
+            if let Some(owner) = cell.player.as_mut() {
+                owner.team = team;
+            }
+        }
+        updated
+    }
+
+    pub fn get_team_members(&self, team: &Team) -> Vec<&Player> {
+        self.players.iter().filter(|player| player.get_team_id() == Some(team.get_id())).collect()
+    }
+
+    // Is every player still alive (or about to respawn) on the same side as this player?
+    pub fn is_last_team_standing(&self, player: &Player) -> bool {
+        self.players.iter()
+            .chain(self.respawns.iter().map(|respawn| &respawn.player))
+            .all(|other| other.is_ally_of(player))
+    }
+
+    // Get the total mass of all of a player's cells
+    pub fn get_player_mass(&self, player: &Player) -> Mass {
+        self.get_player_cells(player).into_iter().fold(Mass(0.0), |total, cell| total + cell.get_mass())
+    }
+
+    // Send all of a player's cells towards their center, so that they merge sooner.
+    // This only lasts for the next tick, so it has to be done every tick the player wants to gather.
+    pub fn gather(&mut self, player: &Player, speed: Speed) {
+        let center = self.get_camera_position(player);
+        self.gathers.insert(player.get_id(), center);
+        if let Some(p) = self.players.iter_mut().find(|p| p.get_id() == player.get_id()) {
+            p.speed = speed;
+        }
+        for cell in self.get_player_cells_mut(player) {
+            let direction = cell.get_position().direction_to(center);
+            cell.set_velocity(direction, speed);
+        }
+    }
+
+    // Get how many seconds until one of a player's cells can split, or None if they have no cells
+    pub fn get_split_cooldown(&self, player: &Player) -> Option<f64> {
+        self.get_player_cells(player).into_iter().map(|cell| (SPLIT_AGE - cell.get_age()).max(0.0)).reduce(f64::min)
+    }
+
     pub fn get_camera_position(&self, player: &Player) -> Position {
         let player_cells = self.get_player_cells(player);
         let player_positions = player_cells.iter().map(|cell| cell.get_position() * cell.get_mass().to_area()).collect::<Vec<_>>();
@@ -79,13 +316,13 @@ impl World {
         cells.sort_by(|a, b| a.get_mass().to_area().partial_cmp(&b.get_mass().to_area()).unwrap());
         cells.reverse();
         let num_cells = cells.len();
-        for cell in cells.iter_mut().filter(|c| c.age > 8.0).take(num_cells / 2 + 2) {
+        for cell in cells.iter_mut().filter(|c| c.age > SPLIT_AGE).take(num_cells / 2 + 2) {
             new_cells.push(cell.mitosis());
         }
 
         new_cells.retain(|cell| cell.get_mass().to_area() > 0.0);
         // Limit to 256 cells
-        new_cells.truncate(256);
+        new_cells.truncate(SPLIT_LIMIT);
 
         for cell in new_cells {
             self.add_entity(Entity::Cell(cell));
@@ -183,20 +420,124 @@ impl World {
         // Every 60 ticks, add new food
         if self.tick % 45 == 0 {
             for _ in 0..100 {
-                let pos = Position(random(), random());
+                let pos = self.random_position();
                 self.add_entity(Entity::Food(Food::new(Mass::default() * (random() + 3.0), pos)));
             }
         }
 
-        let players = self.get_players().into_iter().map(|player| *player).collect::<Vec<_>>();
+        // Total up each player's mass in one pass over the cells
+        let mut masses = HashMap::new();
+        for cell in self.get_cells() {
+            if let Some(id) = cell.get_player_id() {
+                *masses.entry(id).or_insert(0.0) += cell.get_mass().0;
+            }
+        }
+
+        let players = self.get_players().into_iter().copied().collect::<Vec<_>>();
         for player in players {
-            if self.get_player_cells(&player).len() == 0 {
-                self.remove_player(player.get_id());
+            let id = player.get_id();
+            if let Some(mass) = masses.get(&id) {
+                let stats = self.get_stats_mut(id);
+                stats.time_alive += seconds_since_last_tick;
+                stats.peak_mass = Mass(stats.peak_mass.0.max(*mass));
+            } else {
+                self.remove_player(id);
+                self.get_stats_mut(id).deaths += 1;
+                if let Some(delay) = self.config.respawn_delay {
+                    self.respawns.push(Respawn::new(player, delay));
+                }
             }
         }
 
+        // Players stop gathering once they stop asking to
+        self.gathers.clear();
+
         self.tick += 1;
     }
+
+    // Bring back anyone who has waited long enough. Respawn delays are in real seconds,
+    // so this is kept apart from `tick`, whose time step can be sped up or slowed down.
+    pub fn tick_respawns(&mut self, real_seconds_since_last_tick: f64) {
+        for respawn in self.respawns.iter_mut() {
+            respawn.remaining -= real_seconds_since_last_tick;
+        }
+        let (ready, waiting): (Vec<_>, Vec<_>) = self.respawns.drain(..).partition(|respawn| respawn.remaining <= 0.0);
+        self.respawns = waiting;
+        for respawn in ready {
+            self.respawn(respawn.player);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldConfig {
+    // How many seconds an eliminated player waits to respawn, or None to stay eliminated
+    pub respawn_delay: Option<f64>,
+    // The mass of the cell a player respawns with
+    pub respawn_mass: Mass,
+    // A spawn point is good enough if there are no larger cells within this distance
+    pub safe_radius: f64,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        WorldConfig {
+            respawn_delay: None,
+            respawn_mass: Mass::default() * 50.0,
+            safe_radius: 0.2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Respawn {
+    player: Player,
+    // Seconds left until the player comes back
+    remaining: f64,
+}
+
+impl Respawn {
+    pub fn new(player: Player, delay: f64) -> Respawn {
+        Respawn {
+            player,
+            remaining: delay,
+        }
+    }
+
+    pub fn get_player(&self) -> &Player {
+        &self.player
+    }
+
+    pub fn get_remaining(&self) -> f64 {
+        self.remaining
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub deaths: u32,
+    pub food_eaten: u32,
+    // Enemy cells eaten, not counting merging with your own cells
+    pub cells_eaten: u32,
+    pub peak_mass: Mass,
+    // Seconds spent with at least one cell alive
+    pub time_alive: f64,
+    // The last player to eat one of your cells, which is who knocked you out if you have none left
+    pub last_eaten_by: Option<ID>,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats {
+            deaths: 0,
+            food_eaten: 0,
+            cells_eaten: 0,
+            peak_mass: Mass(0.0),
+            time_alive: 0.0,
+            last_eaten_by: None,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
@@ -206,6 +547,16 @@ pub enum Entity {
     Wall(Wall),
 }
 
+impl Entity {
+    pub fn get_position(&self) -> Position {
+        match self {
+            Entity::Cell(cell) => cell.get_position(),
+            Entity::Food(food) => food.get_position(),
+            Entity::Wall(wall) => wall.get_position(),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Wall {
     position: Position,
@@ -235,13 +586,43 @@ impl Wall {
 
         let distance = ((x - x1).powi(2) + (y - y1).powi(2)).sqrt();
         if distance < radius + self.radius {
+            // Push the cell straight out of the wall
             let direction = Direction::from_positions(self.position, cell.get_position());
-            let direction = -direction;
-            cell.move_towards(&direction, distance - radius - self.radius);
+            cell.move_towards(&direction, radius + self.radius - distance);
         }
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct Team {
+    id: ID,
+    color: Color,
+    // Whether teammates are allowed to eat each other's cells
+    friendly_fire: bool,
+}
+
+impl Team {
+    pub fn new(team_id: ID, color: Color, friendly_fire: bool) -> Team {
+        Team {
+            id: team_id,
+            color,
+            friendly_fire,
+        }
+    }
+
+    pub fn get_id(&self) -> ID {
+        self.id
+    }
+
+    pub fn get_color(&self) -> Color {
+        self.color
+    }
+
+    pub fn has_friendly_fire(&self) -> bool {
+        self.friendly_fire
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Player {
     name: [char; 32],
@@ -250,6 +631,7 @@ pub struct Player {
     color: Color,
     direction: Direction,
     speed: Speed,
+    team: Option<Team>,
 }
 
 impl Player {
@@ -261,6 +643,7 @@ impl Player {
             color,
             direction: Direction::from_degrees(random() * 360.0),
             speed: Speed::default() * random(),
+            team: None,
         }
     }
 
@@ -269,8 +652,10 @@ impl Player {
         self.speed = speed;
     }
 
+    // Names are padded out to 32 characters with spaces by `to_name`, which would
+    // otherwise show up in labels and stop skins like `Bot.png` from being found
     pub fn get_name(&self) -> String {
-        self.name.iter().collect()
+        self.name.iter().collect::<String>().trim_end().to_string()
     }
 
     pub fn get_id(&self) -> ID {
@@ -281,8 +666,9 @@ impl Player {
         self.score
     }
 
+    // Players on a team share the team's color
     pub fn get_color(&self) -> Color {
-        self.color
+        self.team.map(|team| team.get_color()).unwrap_or(self.color)
     }
 
     pub fn get_direction(&self) -> Direction {
@@ -292,6 +678,19 @@ impl Player {
     pub fn get_speed(&self) -> Speed {
         self.speed
     }
+
+    pub fn get_team(&self) -> Option<&Team> {
+        self.team.as_ref()
+    }
+
+    pub fn get_team_id(&self) -> Option<ID> {
+        self.team.map(|team| team.get_id())
+    }
+
+    // A player is always an ally of itself, and of anyone on the same team
+    pub fn is_ally_of(&self, other: &Player) -> bool {
+        self.id == other.id || (self.team.is_some() && self.get_team_id() == other.get_team_id())
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
@@ -302,6 +701,10 @@ pub struct Color {
 }
 
 impl Color {
+    pub fn random() -> Color {
+        Color::rgb(random().abs(), random().abs(), random().abs())
+    }
+
     pub fn rgb(r: f64, g: f64, b: f64) -> Color {
         Color {
             r: (r * 255.0) as u8,
@@ -346,6 +749,13 @@ impl Color {
         }
     }
 
+    // Get a darker shade of this color, where 0.0 leaves it as is and 1.0 makes it black
+    pub fn darken(&self, amount: f64) -> Color {
+        let (r, g, b) = self.to_rgb();
+        let scale = 1.0 - amount.clamp(0.0, 1.0);
+        Color::rgb(r * scale, g * scale, b * scale)
+    }
+
     pub fn to_rgb(&self) -> (f64, f64, f64) {
         let Color { r, g, b } = self;
         (f64::from(*r) / 255.0, f64::from(*g) / 255.0, f64::from(*b) / 255.0)
@@ -355,15 +765,14 @@ impl Color {
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
 pub struct ID(u32);
 
-static mut NEXT_ID: u32 = 0;
-
 impl ID {
     pub fn new() -> ID {
-        unsafe {
-            let id = NEXT_ID;
-            NEXT_ID += 1;
+        SEEDED.with(|seeded| {
+            let mut seeded = seeded.borrow_mut();
+            let id = seeded.next_id;
+            seeded.next_id += 1;
             ID(id)
-        }
+        })
     }
 
     pub fn to_number(&self) -> u32 {
@@ -414,6 +823,10 @@ impl Cell {
         }
     }
 
+    pub fn get_age(&self) -> f64 {
+        self.age
+    }
+
     pub fn set_player(&mut self, player: Player) {
         self.player = Some(player);
     }
@@ -475,6 +888,13 @@ impl Cell {
             return false;
         }
 
+        // Teammates can't eat each other unless their team allows it
+        if let (Some(mine), Some(theirs)) = (self.get_player(), other.get_player()) {
+            if mine.get_id() != theirs.get_id() && mine.is_ally_of(theirs) && !mine.get_team().is_some_and(|team| team.has_friendly_fire()) {
+                return false;
+            }
+        }
+
         // Check if the cell is bigger than the other cell
         self.get_mass().to_area() > other.get_mass().to_area() * 1.1
             // Check if the cell is close enough to the other cell
@@ -503,12 +923,14 @@ impl Cell {
         self.apply_friction(seconds_since_last_tick);
 
         // Check if the cell is out of bounds
-        let Position(mut x, mut y) = self.get_position();
-        x = x.max(-1.0).min(1.0);
-        y = y.max(-1.0).min(1.0);
-        self.position = Position(x, y);
+        let bounds = world.get_bounds();
+        let Position(x, y) = self.get_position();
+        self.position = Position(x.clamp(-bounds, bounds), y.clamp(-bounds, bounds));
 
         let mut eaten_ids = Vec::new();
+        let mut food_eaten = 0;
+        let mut cells_eaten = 0;
+        let mut victims = Vec::new();
         for (id, entity) in world.get_entities_mut() {
             if id == my_id {
                 continue;
@@ -518,12 +940,17 @@ impl Cell {
                     if self.can_swallow_food(food) {
                         self.eat_food(food);
                         eaten_ids.push(id);
+                        food_eaten += 1;
                     }
                 }
                 Entity::Cell(cell) => {
                     if self.can_swallow_cell(cell) {
                         self.eat_cell(cell);
                         eaten_ids.push(id);
+                        if cell.get_player_id() != self.get_player_id() {
+                            cells_eaten += 1;
+                            victims.extend(cell.get_player_id());
+                        }
                     }
                 }
                 Entity::Wall(wall) => {
@@ -537,6 +964,15 @@ impl Cell {
             world.remove_entity(id);
         }
 
+        if let Some(id) = self.get_player_id() {
+            let stats = world.get_stats_mut(id);
+            stats.food_eaten += food_eaten;
+            stats.cells_eaten += cells_eaten;
+        }
+        for victim in victims {
+            world.get_stats_mut(victim).last_eaten_by = self.get_player_id();
+        }
+
         // Count number of food available
         let mut num_food = 0;
         for (_, entity) in world.get_entities() {
@@ -547,7 +983,9 @@ impl Cell {
 
         if num_food < 1000 {
             for _ in 0..(10.0 * seconds_since_last_tick).abs().round() as usize {
-                world.add_entity(Entity::Food(Food::default()));
+                let mut food = Food::default();
+                food.set_position(world.random_position());
+                world.add_entity(Entity::Food(food));
             }
         }
 
@@ -560,8 +998,9 @@ impl Cell {
         // Get the current speed and direction from the player
         if let Some(id) = self.get_player_id() {
             if let Some(player) = world.player_from_id(id) {
-                // Update the cell's direction
-                self.set_velocity(player.get_direction(), player.get_speed());
+                // Update the cell's direction, heading for the player's center instead while it's gathering
+                let direction = world.gathers.get(&id).map_or(player.get_direction(), |center| self.get_position().direction_to(*center));
+                self.set_velocity(direction, player.get_speed());
             }
         }
     }
@@ -630,6 +1069,10 @@ impl Food {
     pub fn get_position(&self) -> Position {
         self.position
     }
+
+    pub fn set_position(&mut self, position: Position) {
+        self.position = position;
+    }
     
     pub fn tick(&mut self, my_id: ID, seconds_since_last_tick: f64, _world: &mut World) {
         // Grow the food with compound interest
@@ -935,4 +1378,70 @@ impl Mul<f64> for Speed {
         let Speed(speed) = self;
         Speed(speed * other)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // How far a player's cells are from their center, on average
+    fn spread(world: &World, player: &Player) -> f64 {
+        let center = world.get_camera_position(player);
+        let cells = world.get_player_cells(player);
+        cells.iter().map(|cell| cell.get_position().distance_to(center)).sum::<f64>() / cells.len() as f64
+    }
+
+    #[test]
+    fn respawns_wait_for_real_time() {
+        let mut world = World::new();
+        world.config.respawn_delay = Some(1.0);
+        let player = world.create_empty_player(to_name("Player"), Color::rgb(1.0, 0.0, 0.0));
+
+        // However big a step the simulation takes, the player waits for the delay to pass
+        world.tick(10.0);
+        assert!(world.is_respawning(&player));
+        world.tick_respawns(0.5);
+        assert!(world.is_respawning(&player) && world.get_player_cells(&player).is_empty());
+        world.tick_respawns(0.5);
+        assert!(!world.is_respawning(&player) && !world.get_player_cells(&player).is_empty());
+    }
+
+    #[test]
+    fn names_leave_out_their_padding() {
+        assert_eq!(Player::new(to_name("Bot"), ID::new(), Color::rgb(0.0, 0.0, 1.0)).get_name(), "Bot");
+    }
+
+    #[test]
+    fn walls_push_cells_straight_out() {
+        let wall = Wall::new(Position(0.0, 0.0), 0.1);
+        for position in [Position(0.05, 0.0), Position(0.0, 0.05), Position(-0.03, -0.04)] {
+            let mut cell = Cell::new(Mass::default(), position, Direction::default(), Speed::default(), None);
+            wall.check_collisions(&mut cell);
+            let Position(x, y) = cell.get_position();
+            let expected = 0.1 + cell.get_mass().to_radius();
+            assert!((x.hypot(y) - expected).abs() < 1e-9, "a cell at {:?} ended up at ({}, {})", position, x, y);
+            // The cell keeps to the side of the wall it hit
+            assert!(x * position.0 >= 0.0 && y * position.1 >= 0.0, "a cell at {:?} ended up at ({}, {})", position, x, y);
+        }
+    }
+
+    #[test]
+    fn gathering_pulls_cells_together() {
+        let mut world = World::new();
+        let player = world.create_empty_player(to_name("Player"), Color::rgb(1.0, 0.0, 0.0));
+        for position in [Position(-0.2, 0.0), Position(0.2, 0.0), Position(0.0, 0.2), Position(0.0, -0.2)] {
+            world.add_entity(Entity::Cell(Cell::new(Mass::default(), position, Direction::default(), Speed::default(), Some(player))));
+        }
+        world.set_controls(&player, Direction::default(), Speed::default());
+
+        let before = spread(&world, &player);
+        for _ in 0..10 {
+            world.gather(&player, Speed::default() * 2.0);
+            world.tick(0.05);
+        }
+        assert!(spread(&world, &player) < before * 0.9, "the cells went from {} to {} apart", before, spread(&world, &player));
+
+        // Once the player stops gathering, the cells go back to following the controls
+        world.tick(0.05);
+        assert!(world.gathers.is_empty());
+    }
+}
@@ -1,167 +1,507 @@
-use rand::Rng;
 use ggez::{Context, ContextBuilder, GameResult};
+use ggez::conf::FullscreenType;
 use ggez::graphics::{self, Color};
 use ggez::event::{self, EventHandler};
+use ggez::input::keyboard::{KeyCode, KeyInput};
 use microbiome::*;
-use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use microbiome::ai::{self, Bots, Decision, Difficulty, Profile};
+use microbiome::cli::{self, Args};
+use microbiome::script::Scripts;
 
-const CAMERA_WIDTH: f64 = 1.0 / 8.0;
-const CAMERA_HEIGHT: f64 = 1.0 / 8.0;
+mod camera;
+use camera::Camera;
+
+mod input;
+use input::{Action, Controls};
+
+mod minimap;
+use minimap::Minimap;
+
+mod render;
+use render::Renderer;
+
+// The size of text on a screen without any HiDPI scaling
+const TEXT_SIZE: f32 = 16.0;
+
+// How far the mouse must be from the center of the screen to move at the default speed,
+// as a fraction of the shorter side of the window
+const MOUSE_RANGE: f64 = 1.0 / 6.0;
+
+// How fast the keys and a fully pushed analog stick steer, as a multiple of the default speed
+const STEER_SPEED: f64 = 2.0;
+
+// How many pixels the mouse has to move in a frame to take over from the autopilot
+const MOUSE_NUDGE: f32 = 2.0;
+
+// How many seconds the autopilot waits after the player last steered before it takes over again
+const OVERRIDE_TIME: f64 = 2.0;
+
+// In teams mode, how many bots fight alongside the player, and how many bots are in each enemy squad
+const ALLIES: usize = 2;
+const SQUAD_SIZE: usize = 3;
+
+// The keys used to pick a mode on the main menu
+const MODE_KEYS: [KeyCode; 4] = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+
+// The most bots that can be picked on the settings screen
+const MAX_BOTS: usize = 50;
+
+// How many players are listed in the final standings, besides the player
+const STANDINGS: usize = 10;
 
 fn main() {
-    let mut window_setup = ggez::conf::WindowSetup::default();
-    window_setup.title = "Microbiome🦠".to_string();
+    let options = Options::from_args(std::env::args().skip(1));
+
+    let window_setup = ggez::conf::WindowSetup::default().title("Microbiome🦠");
+    let window_mode = ggez::conf::WindowMode::default().resizable(true);
 
     // Make a Context.
+    // Skins are loaded from `assets/skins`
     let (ctx, event_loop) = ContextBuilder::new("petridish", "Adam McDaniel")
+        .add_resource_path("assets")
         .window_setup(window_setup)
+        .window_mode(window_mode)
         .build()
         .expect("Could not create ggez context!");
 
-    event::run(ctx, event_loop, MyGame::default());
+    event::run(ctx, event_loop, MyGame::main_menu(options));
+}
+
+// Settings picked on the command line, which last across restarts
+#[derive(Clone, Default)]
+struct Options {
+    // Whether the player and the bots are split into teams
+    teams: bool,
+    // The scenario to play instead of the default one
+    scenario: Option<Scenario>,
+    // Only enemy cells at least this heavy are shown on the minimap
+    minimap_threshold: Option<Mass>,
+    controls: Controls,
+    // The AI profile for every bot the scenario doesn't pick one for, instead of a random one
+    profile: Option<Profile>,
+    // Whether the autopilot starts out steering the player
+    autopilot: bool,
+    // The scripts that bots' profiles can ask for, if any were loaded
+    scripts: Option<Scripts>,
+}
+
+impl Options {
+    fn from_args(args: impl Iterator<Item = String>) -> Options {
+        let mut options = Options::default();
+        let mut args = Args::new(args);
+        let mut script = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--teams" => options.teams = true,
+                "--autopilot" => options.autopilot = true,
+                "--scenario" => options.scenario = Some(args.scenario(&arg)),
+                "--profile" => options.profile = Some(args.profile(&arg)),
+                "--scripts" => options.scripts = Some(args.scripts(&arg)),
+                "--script" => script = Some(args.value(&arg, "the name of a script")),
+                "--controls" => {
+                    let path = args.value(&arg, "a path to a controls file");
+                    options.controls = Controls::load(&path).unwrap_or_else(|err| cli::exit(format!("{}: {}", path, err)));
+                }
+                "--minimap-threshold" => {
+                    let threshold = args.parse::<f64>(&arg, "a mass, in multiples of the default mass");
+                    options.minimap_threshold = Some(Mass::default() * threshold);
+                }
+                _ => eprintln!("Ignoring unknown argument {:?}", arg),
+            }
+        }
+
+        // A script takes over the bots' profile, or the default one if no profile was picked
+        if let Some(name) = script {
+            let profile = options.profile.take().unwrap_or_default();
+            options.profile = Some(Profile { script: Some(name), ..profile });
+        }
+        if let Some(script) = options.profile.as_ref().and_then(|profile| profile.script.as_ref()) {
+            cli::require_script(&mut options.scripts, script);
+        }
+        options
+    }
+}
+
+// Which screen the game is on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    MainMenu,
+    Settings,
+    Playing,
+    // The world is frozen until the game is unpaused
+    Paused,
+    // The match goes on while the camera roams freely
+    Spectating,
+    GameOver { won: bool },
+}
+
+// What was picked on the menus, which lasts across restarts
+#[derive(Clone, Copy, Debug, Default)]
+struct Settings {
+    // The index of the mode in `builtin_modes`
+    mode: usize,
+    // The number of bots, or None to leave it up to the mode
+    bots: Option<usize>,
+    difficulty: Difficulty,
+    // The row selected on the settings screen
+    selected: usize,
 }
 
 struct MyGame {
-    game_over: bool,
-    won: bool,
+    state: State,
+    fullscreen: bool,
+    // Whether the debug panel is showing
+    debug: bool,
+    options: Options,
+    settings: Settings,
+    mode: Box<dyn GameMode>,
     // Your state here...
     last_update: std::time::Instant,
+    // How many simulated seconds the last tick covered, and how long it took to run
+    time_step: f64,
+    tick_duration: std::time::Duration,
     this_player: Player,
     world: World,
+    // Everyone who started the match, and the IDs of those who were knocked out of it for good, in order
+    roster: Vec<Player>,
+    eliminations: Vec<ID>,
+    // Whether the player was knocked out, and is watching the rest of the match
+    eliminated: bool,
+    // The player the camera follows while spectating, or None to follow our own player
+    spectated: Option<ID>,
+    camera: Camera,
+    bots: Bots,
+    // What each bot decided to do on the last tick
+    decisions: Vec<Decision>,
+    // Whether the bots' decisions are drawn over the world
+    show_decisions: bool,
+    // Whether the AI steers the player, and how many more seconds it's holding off for
+    // because the player took over
+    autopilot: bool,
+    overridden: f64,
+    // Where the mouse was on the last frame, to tell when it moves
+    mouse: Option<(f32, f32)>,
+    // Created the first time the game is drawn, and kept across restarts
+    renderer: Option<Renderer>,
+    minimap: Option<Minimap>,
 }
 
 impl Default for MyGame {
     fn default() -> MyGame {
-        let name: [char; 32] = ['a'; 32];
-        let color = microbiome::Color::rgb(random().abs(), random().abs(), random().abs());
-        
-        let mut world = World::new();
-        let player = world.create_new_player(name, color);
-        for _ in 0..20 {
-            let pos = Position(random(), random());
-            world.add_entity(Entity::Cell(Cell::new(Mass::default() * 500.0, pos, Direction::from_radians(random() * std::f64::consts::PI), Speed::default(), Some(player))));
+        MyGame::new(Options::default(), Settings::default())
+    }
+}
+
+impl MyGame {
+    // Start on the main menu
+    fn main_menu(options: Options) -> MyGame {
+        MyGame::menu(options, Settings::default(), State::MainMenu)
+    }
+
+    // Wait on one of the menus. There's no match until play starts, just an empty world.
+    fn menu(options: Options, settings: Settings, state: State) -> MyGame {
+        let mode = builtin_modes().into_iter().nth(settings.mode).expect("The selected mode doesn't exist!");
+        let player = Player::new(to_name("Player"), ID::new(), microbiome::Color::random());
+        let mut game = MyGame::with_match(options, settings, mode, World::new(), player, Bots::new(settings.difficulty));
+        game.state = state;
+        game
+    }
+
+    // Start over on the given screen, keeping the window and the renderer as they are.
+    // A new match is only set up when going straight into play.
+    fn restart(&mut self, state: State) {
+        let mut game = match state {
+            State::Playing => MyGame::new(self.options.clone(), self.settings),
+            _ => MyGame::menu(self.options.clone(), self.settings, state),
+        };
+        game.fullscreen = self.fullscreen;
+        game.debug = self.debug;
+        game.show_decisions = self.show_decisions;
+        game.set_autopilot(self.autopilot);
+        game.renderer = self.renderer.take();
+        game.minimap = self.minimap.take();
+        *self = game;
+    }
+
+    // Hand the player over to the AI, with the same profile as the bots, or take them back
+    fn set_autopilot(&mut self, autopilot: bool) {
+        self.autopilot = autopilot;
+        self.overridden = 0.0;
+        if autopilot {
+            self.bots.add(&self.this_player, self.options.profile.clone().unwrap_or_default());
+        } else {
+            self.bots.remove(&self.this_player);
         }
-    
-        let camera_pos = world.get_camera_position(&player);
-    
-        for _ in 0..40 {
-            let name: [char; 32] = ['b'; 32];
-            let color = microbiome::Color::rgb(random().abs(), random().abs(), random().abs());
-            let enemy = world.create_new_player(name, color);
-            let pos = Position(random(), random());
-            let local_range = 0.05 + random() * 0.05;
-            let scale = 15.0 + random() * 10.0;
-            for _ in 0..rand::thread_rng().gen_range(2..10) {
-                // Add random enemy cells
-                
-                let local_pos = pos + Position(random() * local_range, random() * local_range);
-    
-                world.add_entity(Entity::Cell(Cell::new(Mass::default() * scale.powi(2) * (1.0 + random() * 0.1) * 100.0, local_pos, Direction::from_radians(random() * std::f64::consts::PI), Speed::default(), Some(enemy))));
-            }
+    }
+
+    // Get the player the camera is following while spectating, if they're still in the match
+    fn get_spectated(&self) -> Option<Player> {
+        let id = self.spectated?;
+        self.world.get_players().into_iter().find(|player| player.get_id() == id).copied()
+    }
+
+    // Follow the next player still in the match, in the order they joined it
+    fn spectate_next(&mut self) {
+        let mut players = self.world.get_players().into_iter()
+            .filter(|player| player.get_id() != self.this_player.get_id())
+            .map(|player| player.get_id())
+            .collect::<Vec<_>>();
+        players.sort();
+        self.spectated = match self.spectated {
+            Some(current) => players.iter().find(|id| **id > current).or(players.first()).copied(),
+            None => players.first().copied(),
+        };
+    }
+
+    // Rank everyone in the match: those still in it by their score, then everyone
+    // who was knocked out, from the last to go to the first
+    fn get_standings(&self) -> Vec<String> {
+        let mut survivors = self.roster.iter().filter(|player| !self.eliminations.contains(&player.get_id())).collect::<Vec<_>>();
+        survivors.sort_by(|a, b| self.mode.get_score(&self.world, b).partial_cmp(&self.mode.get_score(&self.world, a)).unwrap_or(std::cmp::Ordering::Equal));
+        let eliminated = self.eliminations.iter().rev().filter_map(|id| self.roster.iter().find(|player| player.get_id() == *id));
+
+        survivors.into_iter().chain(eliminated).enumerate().map(|(i, player)| {
+            let stats = self.world.get_stats(player);
+            let you = if player.get_id() == self.this_player.get_id() { " (you)" } else { "" };
+            (i, format!(
+                "{}. {}{}  Peak mass: {:.0}  Cells eaten: {}",
+                i + 1,
+                player.get_name(),
+                you,
+                stats.peak_mass.to_area() / Mass::default().to_area(),
+                stats.cells_eaten,
+            ), !you.is_empty())
+        }).filter(|(i, _, is_you)| *i < STANDINGS || *is_you).map(|(_, line, _)| line).collect()
+    }
+
+    // The number of bots the next match will have
+    fn get_bots(&self) -> usize {
+        self.settings.bots.unwrap_or_else(|| builtin_modes()[self.settings.mode].get_spawn_rules().bots)
+    }
+
+    fn new(options: Options, settings: Settings) -> MyGame {
+        let mode = builtin_modes().into_iter().nth(settings.mode).expect("The selected mode doesn't exist!");
+        let mut rules = mode.get_spawn_rules();
+        if let Some(bots) = settings.bots {
+            rules.bots = bots;
         }
-    
-        world.get_player_cells_mut(&player).into_iter().for_each(|cell| {
-            cell.set_position(Position(random() * 0.1, random() * 0.1));
-        });
-    
-    
-        for _ in 0..2000 {
-            let pos = Position(random(), random());
-            world.add_entity(Entity::Food(Food::new(Mass::default() * (random() + 2.0), pos)));
-            // eprintln!("food added at {:?}", pos);
+        let scenario = options.scenario.clone().unwrap_or_else(|| Scenario::from_spawn_rules(&rules));
+        let (mut world, player, mut profiles) = scenario.build();
+        let mut player = player.expect("scenarios are checked for a human player when they're loaded");
+
+        // Unless the scenario says otherwise, the mode decides whether players respawn
+        if scenario.config.is_none() {
+            world.config.respawn_delay = rules.respawn_delay;
         }
-    
-        for _ in 0..30 {
-            let pos = Position(random(), random());
-            for _ in 0..50 {
-                let local_pos = pos + Position(random() * 0.05, random() * 0.05);
-                world.add_entity(Entity::Food(Food::new(Mass::default() * (random() + 3.0), local_pos)));
-                // eprintln!("food added at {:?}", pos);
+
+        if options.teams {
+            // The first few bots join the player's team, and the rest are split into squads
+            let enemies = world.get_players().into_iter().copied().filter(|p| p.get_id() != player.get_id()).collect::<Vec<_>>();
+            let team = world.create_new_team(player.get_color(), false);
+            player = world.set_team(&player, Some(team));
+
+            let (allies, enemies) = enemies.split_at(ALLIES.min(enemies.len()));
+            for ally in allies {
+                world.set_team(ally, Some(team));
             }
-            // eprintln!("food added at {:?}", pos);
-        }
-    
-        let name: [char; 32] = ['a'; 32];
-        let color = microbiome::Color::rgb(random().abs(), random().abs(), random().abs());
-        
-        let mut world = World::new();
-        let player = world.create_new_player(name, color);
-        for _ in 0..10 {
-            let pos = Position(random(), random());
-            world.add_entity(Entity::Cell(Cell::new(Mass::default() * 50.0, pos, Direction::from_radians(random() * std::f64::consts::PI), Speed::default(), Some(player))));
-        }
-    
-        let camera_pos = world.get_camera_position(&player);
-    
-        for _ in 0..10 {
-            let name: [char; 32] = ['b'; 32];
-            let color = microbiome::Color::rgb(random().abs(), random().abs(), random().abs());
-            let enemy = world.create_new_player(name, color);
-            let pos = Position(random(), random());
-            let local_range = 0.05 + random() * 0.05;
-            for _ in 0..rand::thread_rng().gen_range(2..10) {
-                // Add random enemy cells
-                
-                let local_pos = pos + Position(random() * local_range, random() * local_range);
-    
-                world.add_entity(Entity::Cell(Cell::new(Mass::default() * 500.0, local_pos, Direction::from_radians(random() * std::f64::consts::PI), Speed::default(), Some(enemy))));
+
+            for squad in enemies.chunks(SQUAD_SIZE) {
+                let team = world.create_new_team(microbiome::Color::random(), false);
+                for enemy in squad {
+                    world.set_team(enemy, Some(team));
+                }
             }
         }
-    
-        world.get_player_cells_mut(&player).into_iter().for_each(|cell| {
-            cell.set_position(Position(random() * 0.1, random() * 0.1));
-        });
-    
-    
-        for _ in 0..2000 {
-            let pos = Position(random(), random());
-            world.add_entity(Entity::Food(Food::new(Mass::default() * (random() + 2.0), pos)));
-            // eprintln!("food added at {:?}", pos);
-        }
-    
-        for _ in 0..20 {
-            let pos = Position(random(), random());
-            for _ in 0..50 {
-                let local_pos = pos + Position(random() * 0.05, random() * 0.05);
-                world.add_entity(Entity::Food(Food::new(Mass::default() * (random() + 2.0), local_pos)));
-                // eprintln!("food added at {:?}", pos);
-            }
-            // eprintln!("food added at {:?}", pos);
+
+        // Every other player is a bot, playing however the scenario or the command line says
+        let mut bots = Bots::new(settings.difficulty);
+        for bot in world.get_players().into_iter().filter(|p| p.get_id() != player.get_id()) {
+            let profile = profiles.remove(&bot.get_id()).or_else(|| options.profile.clone()).unwrap_or_else(Profile::random);
+            bots.add(bot, profile);
         }
+        bots.set_scripts(options.scripts.clone().unwrap_or_default());
     
+        MyGame::with_match(options, settings, mode, world, player, bots)
+    }
+
+    fn with_match(options: Options, settings: Settings, mode: Box<dyn GameMode>, world: World, player: Player, bots: Bots) -> MyGame {
         // Create an instance of your event handler.
         // Usually, you should provide it with the Context object to
         // use when setting your game up.
-        MyGame {
-            game_over: false,
-            won: false,
+        let autopilot = options.autopilot;
+        let mut game = MyGame {
+            state: State::Playing,
+            fullscreen: false,
+            debug: false,
+            options,
+            settings,
+            mode,
             last_update: std::time::Instant::now(),
+            time_step: 0.0,
+            tick_duration: std::time::Duration::ZERO,
             this_player: player,
+            roster: world.get_players().into_iter().copied().collect(),
+            eliminations: Vec::new(),
+            eliminated: false,
+            spectated: None,
             world,
-        }
+            camera: Camera::new(),
+            bots,
+            decisions: Vec::new(),
+            show_decisions: false,
+            autopilot: false,
+            overridden: 0.0,
+            mouse: None,
+            renderer: None,
+            minimap: None,
+        };
+        game.set_autopilot(autopilot);
+        game
     }
 }
 
 impl EventHandler for MyGame {
     fn update(&mut self, ctx: &mut Context) -> GameResult {
-        if self.game_over {
-            // Detect spacebar to restart
-            if ctx.keyboard.is_key_just_pressed(ggez::input::keyboard::KeyCode::Space) {
-                // Reset the game
-                *self = MyGame::default();
+        self.options.controls.update(ctx);
+        let controls = &self.options.controls;
+
+        if controls.is_just_pressed(ctx, Action::Fullscreen) {
+            self.fullscreen = !self.fullscreen;
+            let fullscreen_type = if self.fullscreen { FullscreenType::Desktop } else { FullscreenType::Windowed };
+            ctx.gfx.set_fullscreen(fullscreen_type)?;
+        }
+
+        if controls.is_just_pressed(ctx, Action::Debug) {
+            self.debug = !self.debug;
+        }
+
+        if controls.is_just_pressed(ctx, Action::Decisions) {
+            self.show_decisions = !self.show_decisions;
+        }
+
+        if controls.is_just_pressed(ctx, Action::Minimap) {
+            if let Some(minimap) = &mut self.minimap {
+                minimap.toggle();
             }
-            return Ok(());
         }
 
+        if controls.is_just_pressed(ctx, Action::MassLabels) {
+            if let Some(renderer) = &mut self.renderer {
+                renderer.toggle_mass_labels();
+            }
+        }
+
+        match self.state {
+            State::MainMenu => {
+                let modes = builtin_modes().len();
+                if controls.is_just_pressed(ctx, Action::Up) {
+                    self.settings.mode = (self.settings.mode + modes - 1) % modes;
+                }
+                if controls.is_just_pressed(ctx, Action::Down) {
+                    self.settings.mode = (self.settings.mode + 1) % modes;
+                }
+                if controls.is_just_pressed(ctx, Action::Settings) {
+                    self.state = State::Settings;
+                    return Ok(());
+                }
+                if controls.is_just_pressed(ctx, Action::Pause) {
+                    ctx.request_quit();
+                    return Ok(());
+                }
+
+                // Start a new game in whichever mode was picked
+                let picked = MODE_KEYS.iter().take(modes).position(|key| ctx.keyboard.is_key_just_pressed(*key));
+                if let Some(mode) = picked {
+                    self.settings.mode = mode;
+                }
+                if picked.is_some() || controls.is_just_pressed(ctx, Action::Confirm) || controls.is_just_pressed(ctx, Action::Split) {
+                    self.restart(State::Playing);
+                }
+                return Ok(());
+            }
+            State::Settings => {
+                if controls.is_just_pressed(ctx, Action::Up) || controls.is_just_pressed(ctx, Action::Down) {
+                    self.settings.selected = 1 - self.settings.selected;
+                }
+
+                let change = controls.is_just_pressed(ctx, Action::Right) as isize - controls.is_just_pressed(ctx, Action::Left) as isize;
+                if change != 0 {
+                    if self.settings.selected == 0 {
+                        self.settings.bots = Some(self.get_bots().saturating_add_signed(change).min(MAX_BOTS));
+                    } else {
+                        let levels = Difficulty::all();
+                        let level = levels.iter().position(|level| *level == self.settings.difficulty).unwrap_or(0);
+                        self.settings.difficulty = levels[level.saturating_add_signed(change).min(levels.len() - 1)];
+                    }
+                }
+
+                if controls.is_just_pressed(ctx, Action::Confirm) || controls.is_just_pressed(ctx, Action::Pause) || controls.is_just_pressed(ctx, Action::Settings) {
+                    self.state = State::MainMenu;
+                }
+                return Ok(());
+            }
+            State::GameOver { .. } => {
+                if controls.is_just_pressed(ctx, Action::Confirm) || controls.is_just_pressed(ctx, Action::Split) {
+                    self.restart(State::MainMenu);
+                }
+                return Ok(());
+            }
+            State::Paused => {
+                // No time passes for the world while it's paused, so there's no jump when it resumes
+                self.last_update = std::time::Instant::now();
+                if controls.is_just_pressed(ctx, Action::Pause) {
+                    self.state = State::Playing;
+                } else if controls.is_just_pressed(ctx, Action::Confirm) {
+                    self.restart(State::MainMenu);
+                }
+                return Ok(());
+            }
+            State::Playing | State::Spectating => {
+                if controls.is_just_pressed(ctx, Action::Pause) {
+                    self.state = State::Paused;
+                    return Ok(());
+                }
+
+                // Spectating frees the camera to look around the dish with the steering keys.
+                // Once the player is knocked out, it switches between the free camera and following someone else.
+                if controls.is_just_pressed(ctx, Action::Spectate) {
+                    if self.eliminated {
+                        self.camera.set_free(!self.camera.is_free());
+                    } else {
+                        self.state = if self.state == State::Spectating { State::Playing } else { State::Spectating };
+                        self.camera.set_free(self.state == State::Spectating);
+                        self.spectated = None;
+                    }
+                }
+
+                // Follow the next player still in the match
+                if self.state == State::Spectating && (controls.is_just_pressed(ctx, Action::Confirm) || controls.is_just_pressed(ctx, Action::Split)) {
+                    self.spectate_next();
+                    self.camera.set_free(false);
+                }
+
+                if self.options.controls.is_just_pressed(ctx, Action::Autopilot) {
+                    self.set_autopilot(!self.autopilot);
+                }
+            }
+        }
+
+        let controls = &self.options.controls;
+
         // Update code here...
         // Get mouse position
         let mouse_pos = ctx.mouse.position();
         let mouse_x = mouse_pos.x;
         let mouse_y = mouse_pos.y;
 
-        // Position of the center of the screen
-        let center_x = 400.0;
-        let center_y = 300.0;
+        // Steer relative to where the player is on the screen, which is
+        // not quite the center while the camera is catching up
+        let (screen_width, screen_height) = ctx.gfx.drawable_size();
+        let [center_x, center_y] = if self.world.get_player_cells(&self.this_player).is_empty() {
+            [screen_width / 2.0, screen_height / 2.0]
+        } else {
+            self.camera.get_view(screen_width as f64, screen_height as f64).project(self.world.get_camera_position(&self.this_player))
+        };
 
         // Get the distance between the mouse and the center of the screen
         let distance = (((mouse_x - center_x).powf(2.0) + (mouse_y - center_y).powf(2.0)).sqrt()) as f64;
@@ -169,485 +509,296 @@ impl EventHandler for MyGame {
         // Get the angle of the mouse
         let direction = Direction::from_radians((mouse_y - center_y).atan2(mouse_x - center_x).into());
 
-        let speed = Speed::default() * (distance / 100.0);
+        // The same mouse movement should give the same speed at any window size
+        let mut speed = Speed::default() * (distance / (screen_width.min(screen_height) as f64 * MOUSE_RANGE));
+        let mut direction = direction;
 
-        // Get the velocity of the player
-        self.this_player.set_velocity(direction, speed);
-        self.world.set_controls(&self.this_player, direction, speed);
-
-        // Target the player with median total mass
-
-        let highest_median_mass = self.world.get_players().into_iter().map(|player| {
-            let mut cells = self.world.get_player_cells(&player);
-            cells.sort_by(|a, b| {
-                a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal)
-            });
-            cells[cells.len()/2].get_mass().to_area()
-        }).max_by(|a, b| a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)).unwrap();
-
-        let avg_population = self.world.get_players().into_iter().map(|player| {
-            self.world.get_player_cells(&player).len()
-        }).sum::<usize>() as f64 / self.world.get_players().len() as f64;
-
-        let highest_population = self.world.get_players().into_iter().map(|player| {
-            self.world.get_player_cells(&player).len()
-        }).max_by(|a, b| a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)).unwrap() as f64;
-
-        let players = self.world.get_players().into_iter().map(|player| *player).collect::<Vec<_>>();
-          
-        let controls = players.par_iter().map(|player| {
-            let avg_position = self.world.get_camera_position(&player);
-            let target_player = {
-                let mut players = self.world.get_players().into_iter().map(|player| *player).filter(|p| p != player).collect::<Vec<_>>();
-
-                players.sort_by(|a, b| {
-                    // let smallest_a_cell = self.world.get_player_cells(&a).into_iter().min_by(|a, b| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal)).unwrap();
-                    // let smallest_b_cell = self.world.get_player_cells(&b).into_iter().min_by(|a, b| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal)).unwrap();
-                    let a_pos = self.world.get_camera_position(&a);
-                    let b_pos = self.world.get_camera_position(&b);
-                    let a_mass = self.world.get_player_cells(&a).into_iter().map(|cell| cell.get_mass().to_area()).sum::<f64>() / a_pos.distance_to(avg_position).powi(1);
-                    let b_mass = self.world.get_player_cells(&b).into_iter().map(|cell| cell.get_mass().to_area()).sum::<f64>() / b_pos.distance_to(avg_position).powi(1);
-                    a_mass.partial_cmp(&b_mass).unwrap_or(std::cmp::Ordering::Equal)
-                });
-                // players[players.len() / 2]
-                // if players.len() < 6 {
-                //     players[players.len() - 1]
-                // } else {
-                //     players[players.len()/2]
-                // }
-                players[0]
-            };
-
-            if self.world.get_player_cells(&target_player).is_empty() {
-                return None;
-            }
+        // The keys and gamepad take over from the mouse while they're in use
+        let steering = controls.get_steering(ctx);
+        if let Some((x, y)) = steering {
+            direction = Direction::from_vector(x, y);
+            speed = Speed::default() * (STEER_SPEED * x.hypot(y));
+        }
 
-            let smallest_cell = self.world.get_player_cells(&target_player).into_iter().min_by(|a, b| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal)).unwrap();
-            let largest_cell = self.world.get_player_cells(&target_player).into_iter().max_by(|a, b| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal)).unwrap();
-            let smallest_cell_pos = smallest_cell.get_position();
-            let smallest_cell_mass = smallest_cell.get_mass().to_area();
-            let largest_cell_mass = largest_cell.get_mass().to_area();
-             
-            if *player != self.this_player {
-                // Get smallest cell for this player
-                let enemy_cell = self.world.get_player_cells(&player).into_iter().max_by(|a, b| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal)).unwrap();
-                let enemy_cell_pos = enemy_cell.get_position();
-                let enemy_cell_mass = enemy_cell.get_mass().to_area();
-                let speed = Speed::default() * 3.5;
-                let smallest_enemy_cell = self.world.get_player_cells(&player).into_iter().min_by(|a, b| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal)).unwrap();
-                let smallest_enemy_cell_mass = smallest_enemy_cell.get_mass().to_area();
-
-                
-
-                let closest_enemy_cell = self.world.get_player_cells(&player).into_iter().min_by(|a, b| {
-                    let a_distance = a.get_position().distance_to(smallest_cell_pos);
-                    let b_distance = b.get_position().distance_to(smallest_cell_pos);
-                    a_distance.partial_cmp(&b_distance).unwrap_or(std::cmp::Ordering::Equal)
-                }).unwrap();
-
-                let avg_position = self.world.get_camera_position(&player);
-
-
-                let mut weighted_directions = self.world.get_entities().par_iter().filter_map(|(_, entity)| {
-                    match entity {
-                        Entity::Food(food) => {
-                            // Some(food.get_position() * ((food_mulitplier * food.to_mass().to_area()).powi(2) / food.get_position().distance_to(avg_position).powi(1)))
-                            // Return direction towards food
-                            let food_mass = food.to_mass().to_area();
-                            let food_pos = food.get_position();
-                            let food_distance = food_pos.distance_to(avg_position);
-                            
-                            let is_close = food_distance < food.to_mass().to_radius() * 200.0;
-                            if is_close {
-                                let closest_cell = self.world.get_player_cells(&player).into_iter().min_by(|a, b| {
-                                    let a_distance = a.get_position().distance_to(food_pos);
-                                    let b_distance = b.get_position().distance_to(food_pos);
-                                    a_distance.partial_cmp(&b_distance).unwrap_or(std::cmp::Ordering::Equal)
-                                }).unwrap();
-                                Some((5.0 * food_mass / food_distance.powi(2), closest_cell.get_position().direction_to(food_pos), false))
-
-                            } else {
-                                Some((3.0 * food_mass / food_distance.powi(2), avg_position.direction_to(food_pos), false))
-                            }
-                        },
-                        Entity::Cell(cell) => {
-                            if cell.get_player() == Some(&player) {
-                                return None;
-                            }
-                            let cell_pos = cell.get_position();
-                            let cell_mass = cell.get_mass().to_area();
-                            
-                            // Get closest cell to this cell
-                            let closest_cell = self.world.get_player_cells(&player).into_iter().min_by(|a, b| {
-                                let a_distance = a.get_position().distance_to(cell_pos);
-                                let b_distance = b.get_position().distance_to(cell_pos);
-                                a_distance.partial_cmp(&b_distance).unwrap_or(std::cmp::Ordering::Equal)
-                            }).unwrap();
-                            let closest_cell_mass = closest_cell.get_mass().to_area();
-                            let closest_cell_pos = closest_cell.get_position();
-                            let cell_distance = cell.get_position().distance_to(closest_cell_pos);
-                            let is_close = cell_distance < cell.get_mass().to_radius().max(closest_cell.get_mass().to_radius()) * 32.0;
-                            if !is_close {
-                                return None;
-                            }
-
-                            if cell_mass < closest_cell_mass * 0.3 {
-                                // Some(cell.get_position() * (cell.get_mass().to_area().powi(4) / cell.get_position().distance_to(closest_cell.get_position()).powi(2)))
-                                // Some(avg_position.move_towards(&avg_position.direction_to(cell.get_position()), cell.get_mass().to_area().powi(4) / cell.get_position().distance_to(closest_cell.get_position()).powi(1)))
-                                Some((10.0 * cell_mass / cell_distance.powi(2), closest_cell_pos.direction_to(cell.get_position()), false))
-                            } else if cell_mass < closest_cell_mass * 0.6 {
-                                // Some(cell.get_position() * (cell.get_mass().to_area().powi(3) / cell.get_position().distance_to(closest_cell.get_position()).powi(2)))
-                                // Some(avg_position.move_towards(&avg_position.direction_to(cell.get_position()), cell.get_mass().to_area().powi(3) / cell.get_position().distance_to(closest_cell.get_position()).powi(1)))
-                            // } else if cell.get_mass().to_area() < closest_cell_mass * 0.8 {
-                                // Some(cell.get_position() * (cell.get_mass().to_area().powi(2) / cell.get_position().distance_to(closest_cell.get_position()).powi(2)))
-                                // Some(avg_position.move_towards(&avg_position.direction_to(cell.get_position()), cell.get_mass().to_area().powi(2) / cell.get_position().distance_to(closest_cell.get_position()).powi(1)))
-                                Some((8.0 * cell_mass / cell_distance.powi(2), closest_cell_pos.direction_to(cell.get_position()), false))
-                            // Detect if close to a large, threatening cell
-                            } else if cell.get_mass().to_area() < closest_cell_mass {
-                                // Some(cell.get_position() * (cell.get_mass().to_area().powi(3) / cell.get_position().distance_to(closest_cell.get_position()).powi(2)))
-                                // Some(avg_position.move_towards(&avg_position.direction_to(cell.get_position()), cell.get_mass().to_area().powi(3) / cell.get_position().distance_to(closest_cell.get_position()).powi(1)))
-                            // } else if cell.get_mass().to_area() < closest_cell_mass * 0.8 {
-                                // Some(cell.get_position() * (cell.get_mass().to_area().powi(2) / cell.get_position().distance_to(closest_cell.get_position()).powi(2)))
-                                // Some(avg_position.move_towards(&avg_position.direction_to(cell.get_position()), cell.get_mass().to_area().powi(2) / cell.get_position().distance_to(closest_cell.get_position()).powi(1)))
-                                Some((6.0 * cell_mass / cell_distance.powi(2), closest_cell_pos.direction_to(cell.get_position()), false))
-                            // Detect if close to a large, threatening cell
-                            } else if cell.get_mass().to_area() > closest_cell_mass {
-                                // food_mulitplier = food_mulitplier.powi(2);
-                                // Some(avg_position.move_away(&avg_position.direction_to(cell.get_position()), 0.))
-                                // None
-                                // let direction_to_threat = cell.get_position().direction_to(avg_position);
-                                // let heading = Direction::from_radians(direction_to_threat.to_radians() * (1.0 + random() * 0.2));
-                                // Some(avg_position.move_towards(&heading, (cell.get_mass().to_area().powi(2) + closest_cell_mass.powi(2)) / cell.get_position().distance_to(closest_cell.get_position()).powi(2)))
-                                Some((-10.0 * closest_cell_mass / cell_distance.powi(2), closest_cell_pos.direction_to(cell_pos), true))
-                            } else {
-                                None
-                            }
-                        },
-                        _ => None,
-                    }
-                }).collect::<Vec<_>>();
-
-                // Calculate target position based on weighted average
-                // let weights: Vec<_> = weighted_directions.iter().map(|(weight, _)| *weight).collect();
-                // let sum_of_weights = weights.iter().sum::<f64>();
-                // let directions = weighted_directions.iter().map(|(_, direction)| direction).collect::<Vec<_>>();
-
-                let mut x = 0.0;
-                let mut y = 0.0;
-            
-                for (weight, direction, _) in &weighted_directions {
-                    x += weight * direction.x_component();
-                    y += weight * direction.y_component();
-                }
-            
-                // Calculate the average angle
-                let target_direction = Direction::from_vector(x, y);
-
-                // println!("Targeting direction {:?}", target_direction);
-
-                // let highest_avg_mass = self.world.get_players().into_iter().map(|player| {
-                //     let avg_mass = self.world.get_player_cells(&player).into_iter().map(|cell| cell.get_mass().to_area()).sum::<f64>() / self.world.get_player_cells(&player).len() as f64;
-                //     avg_mass
-                // }).max_by(|a, b| a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)).unwrap();
-
-                // // Decide whether to perform mitosis
-                // let median_mass = {
-                //     let mut cells = self.world.get_player_cells(&player);
-                //     cells.sort_by(|a, b| {
-                //         a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal)
-                //     });
-                //     cells[cells.len()/2].get_mass().to_area()
-                // };
-
-                // let highest_population = self.world.get_players().into_iter().map(|player| {
-                //     self.world.get_player_cells(&player).len()
-                // }).max_by(|a, b| a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)).unwrap() as f64;
-                // let median_population = self.world.get_players().into_iter().map(|player| {
-                //     self.world.get_player_cells(&player).len()
-                // }).nth(self.world.get_players().len() / 2).unwrap() as f64;
-
-                // let direction = avg_position.direction_to(target_pos);
-                
-                // // let direction = Direction::from_radians(player.get_direction().to_radians() * (1.0 + random() * 0.5));
-                // println!("Randomly moving player");
-                // if smallest_cell_mass < enemy_cell_mass * 0.5 && closest_enemy_cell.get_position().distance_to(avg_position) < 0.1 {
-                //     // Eat the enemy
-                //     // let avg_position = self.world.get_camera_position(&player);
-                //     let direction = closest_enemy_cell.get_position().direction_to(smallest_cell_pos);
-                //     println!("Pointing to enemy at {:?}", smallest_cell_pos);
-                //     self.world.set_controls(&player, direction, speed);
-                //     // Count cells
-                //     let num_cells = self.world.get_player_cells(&player).len();
-                //     // Calculate average mass
-                //     let avg_mass = self.world.get_player_cells(&player).into_iter().map(|cell| cell.get_mass().to_area()).sum::<f64>() / num_cells as f64;
-                //     if avg_mass > Mass::default().to_area() * 100.0 && num_cells < 4 {
-                //         self.world.mitosis(&player);
-                //     } else if largest_cell_mass < smallest_enemy_cell_mass * 0.5 && num_cells < 8 && closest_enemy_cell_mass < smallest_enemy_cell_mass * 0.5 {
-                //         self.world.mitosis(&player);
-                //     }
-                // } else if smallest_cell_mass < smallest_enemy_cell_mass && closest_enemy_cell.get_position().distance_to(avg_position) < 0.1 {
-                //     // Run away
-                //     // let avg_position = self.world.get_camera_position(&player);
-                //     let direction = closest_enemy_cell.get_position().direction_to(smallest_cell_pos);
-                //     println!("Pointing to enemy at {:?}", smallest_cell_pos);
-                //     self.world.set_controls(&player, direction, speed);
-                // } else {
-                //     // Point towards food
-                //     let food = self.world.get_entities().into_iter().filter_map(|(id, entity)| {
-                //         match entity {
-                //             Entity::Food(food) => {
-                //                 Some((id, food))
-                //             },
-                //             _ => None,
-                //         }
-                //     }).min_by(|(_, a), (_, b)| {
-                //         let a_distance = a.get_position().distance_to(avg_position) * a.to_mass().to_area();
-                //         let b_distance = b.get_position().distance_to(avg_position) * b.to_mass().to_area();
-                //         a_distance.partial_cmp(&b_distance).unwrap_or(std::cmp::Ordering::Equal)
-                //     }).map(|(id, food)| {
-                //         (id, food.get_position())
-                //     });
-
-                //     let direction = if let Some((_, food_pos)) = food {
-                //         avg_position.direction_to(food_pos)
-                //     } else {
-                //         Direction::from_radians(random() * std::f64::consts::PI * 2.0)
-                //     };                    
-                //     // let direction = Direction::from_radians(player.get_direction().to_radians() * (1.0 + random() * 0.5));
-                //     println!("Randomly moving player");
-                //     self.world.set_controls(&player, direction, speed);
-                // }
-                return Some((player, target_direction, speed, weighted_directions.iter().map(|(_, _, should_split)| *should_split).any(|should_split| should_split)))
-            } else {
-                // return (self.this_player, Direction::from_radians(random() * std::f64::consts::PI * 2.0), Speed::default())
-                return None
+        // Moving the mouse or using the controls takes over from the autopilot for a little while
+        let nudge = MOUSE_NUDGE * ctx.gfx.window().scale_factor() as f32;
+        let nudged = self.mouse.is_some_and(|(x, y)| (mouse_x - x).hypot(mouse_y - y) > nudge);
+        self.mouse = Some((mouse_x, mouse_y));
+        if self.autopilot && self.state == State::Playing && (nudged || steering.is_some() || controls.is_pressed(ctx, Action::Merge) || controls.is_just_pressed(ctx, Action::Split)) {
+            self.overridden = OVERRIDE_TIME;
+        }
+        let autopiloting = self.autopilot && self.overridden <= 0.0;
+
+        // While spectating, the player's cells are left to drift and the steering moves the camera.
+        // The autopilot keeps playing either way.
+        if self.state == State::Playing && !autopiloting {
+            self.this_player.set_velocity(direction, speed);
+            self.world.set_controls(&self.this_player, direction, speed);
+            if controls.is_pressed(ctx, Action::Merge) {
+                self.world.gather(&self.this_player, Speed::default() * STEER_SPEED);
             }
-        }).collect::<Vec<_>>();
-        for (player, target_direction, speed, should_split) in controls.into_iter().filter(|control| control.is_some()).map(|control| control.unwrap()) {
-            self.world.set_controls(&player, target_direction, speed);
-            
-            let my_population = self.world.get_player_cells(&player).len() as f64;
-            // Calculate average mass
-            // let my_avg_mass = self.world.get_player_cells(&player).into_iter().map(|cell| cell.get_mass().to_area()).sum::<f64>() / my_population;
-            let my_median_mass = {
-                let mut cells = self.world.get_player_cells(&player);
-                cells.sort_by(|a, b| {
-                    a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal)
-                });
-                cells[cells.len()/2].get_mass().to_area()
-            };
-
-            if should_split && my_population < 512.0 && (my_median_mass.log10() / highest_median_mass.log10() > 2.0 || my_population.log10() / highest_population.log10() < 0.5) {
-                self.world.mitosis(&player);
+            if controls.is_just_pressed(ctx, Action::Split) {
+                self.world.mitosis(&self.this_player);
             }
         }
-        // self.world.set_controls(&player, target_direction, speed);
 
-        // detect spacebar
-        if ctx.keyboard.is_key_just_released(ggez::input::keyboard::KeyCode::Space) {
-            // Split the player
-            self.world.mitosis(&self.this_player);
+        // Let the bots decide what to do, keeping their reasons around for the debug overlay
+        self.decisions = self.bots.think(&self.world, self.last_update.elapsed().as_secs_f64());
+        for error in self.bots.take_errors() {
+            eprintln!("{}", error);
+        }
+        if !autopiloting {
+            self.decisions.retain(|decision| decision.player.get_id() != self.this_player.get_id());
         }
+        ai::act(&mut self.world, &self.decisions);
 
         let median_cell_mass = {
             let mut cells = self.world.get_player_cells(&self.this_player);
             cells.sort_by(|a, b| {
                 a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal)
             });
-            cells[cells.len()/5].get_mass().to_area()
+            // While waiting to respawn, fall back on the default mass
+            cells.get(cells.len()/5).map(|cell| cell.get_mass().to_area()).unwrap_or(Mass::default().to_area())
         };
 
         // Update the world
         // let seconds_since_last_update = ctx.time.delta().as_secs_f64();
         // let seconds_since_last_update = 0.0;
-        let seconds_since_last_update = self.last_update.elapsed().as_secs_f64() * 2.0 * median_cell_mass.log10() / Mass::default().to_area().log10();
+        let real_seconds_since_last_update = self.last_update.elapsed().as_secs_f64();
+        let seconds_since_last_update = real_seconds_since_last_update * 2.0 * median_cell_mass.log10() / Mass::default().to_area().log10();
         self.last_update = std::time::Instant::now();
+        self.overridden = (self.overridden - real_seconds_since_last_update).max(0.0);
+        let tick_start = std::time::Instant::now();
         self.world.tick(seconds_since_last_update);
-
-
-
-        // Detect win
-        if self.world.get_players().len() == 1 && self.world.get_player_cells(&self.this_player).len() > 0 {
-            self.won = true;
-            self.game_over = true;
+        self.world.tick_respawns(real_seconds_since_last_update);
+        self.tick_duration = tick_start.elapsed();
+        self.time_step = seconds_since_last_update;
+        self.mode.tick(&mut self.world, real_seconds_since_last_update);
+
+        // Keep track of who's been knocked out for good, for the final standings
+        for player in &self.roster {
+            let id = player.get_id();
+            if !self.eliminations.contains(&id) && self.world.get_players().into_iter().all(|other| other.get_id() != id) && !self.world.is_respawning(player) {
+                self.eliminations.push(id);
+            }
         }
 
-        if self.won {
-            return Ok(());
+        // Move on from a spectated player once they're eaten
+        if self.spectated.is_some() && self.get_spectated().is_none() {
+            self.spectate_next();
         }
 
-        // Detect game over
-        if self.world.get_cells().len() == 0 {
-            self.game_over = true;
-            self.won = false;
+        if self.camera.is_free() {
+            let (x, y) = steering.unwrap_or((0.0, 0.0));
+            self.camera.pan(x, y, real_seconds_since_last_update);
+        } else {
+            let followed = self.get_spectated().unwrap_or(self.this_player);
+            self.camera.follow(&self.world, &followed);
         }
-
-        if self.world.get_player_cells(&self.this_player).len() == 0 {
-            self.game_over = true;
-            self.won = false;
+        self.camera.update(real_seconds_since_last_update);
+
+        // Detect win or game over
+        if !self.eliminated {
+            match self.mode.get_outcome(&self.world, &self.this_player) {
+                Outcome::Won => self.state = State::GameOver { won: true },
+                // Once the player is eaten for good, they can watch the rest of the match
+                Outcome::Lost if self.world.get_player_cells(&self.this_player).is_empty() => {
+                    self.eliminated = true;
+                    self.state = State::Spectating;
+                    self.camera.set_free(false);
+                    self.spectate_next();
+                }
+                Outcome::Lost => self.state = State::GameOver { won: false },
+                Outcome::Undecided => {}
+            }
         }
 
-        if self.game_over {
-            return Ok(());
+        // The match is over once someone still in it has won, which is a win for the player too if they're on the same team
+        if self.eliminated {
+            let players = self.world.get_players();
+            let winner = players.iter().find(|player| self.mode.get_outcome(&self.world, player) == Outcome::Won);
+            if players.is_empty() || winner.is_some() {
+                let won = winner.is_some_and(|winner| winner.is_ally_of(&self.this_player));
+                self.state = State::GameOver { won };
+            }
         }
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
         let mut canvas = graphics::Canvas::from_frame(ctx, Color::WHITE);
-        let (screen_width, screen_height) = (800.0_f64, 600.0_f64);
-        if self.won {
-            // Game over
-            let text = graphics::Text::new("You Win!");
-            let text_x = screen_width as f32 / 2.0;
-            let text_y = screen_height as f32 / 2.0;
-            let color = Color::from_rgb(0, 0, 0);
-            let text_pos = graphics::DrawParam::default().dest([text_x, text_y]).color(color);
-            canvas.draw(&text, text_pos);
-            return canvas.finish(ctx);
+        let (screen_width, screen_height) = ctx.gfx.drawable_size();
+        let (screen_width, screen_height) = (screen_width as f64, screen_height as f64);
+        let (center_x, center_y) = (screen_width as f32 / 2.0, screen_height as f32 / 2.0);
+        let score = self.mode.get_score(&self.world, &self.this_player);
+        match self.state {
+            State::MainMenu => {
+                let mut lines = vec!["Choose a game mode:".to_string()];
+                for (i, mode) in builtin_modes().iter().enumerate() {
+                    let marker = if i == self.settings.mode { ">" } else { " " };
+                    lines.push(format!("{} {}. {} - {}", marker, i + 1, mode.get_name(), mode.get_description()));
+                }
+                lines.push(String::new());
+                lines.push(format!("Bots: {}  Difficulty: {}", self.get_bots(), self.settings.difficulty.get_name()));
+                lines.push("Enter to play, Tab for settings, Escape to quit".to_string());
+                draw_text(ctx, &mut canvas, lines.join("\n"), screen_width as f32 / 4.0, center_y);
+                return canvas.finish(ctx);
+            }
+            State::Settings => {
+                let rows = [
+                    format!("Bots: < {} >", self.get_bots()),
+                    format!("Difficulty: < {} >", self.settings.difficulty.get_name()),
+                ];
+                let mut lines = vec!["Settings".to_string(), String::new()];
+                for (i, row) in rows.iter().enumerate() {
+                    let marker = if i == self.settings.selected { ">" } else { " " };
+                    lines.push(format!("{} {}", marker, row));
+                }
+                lines.push(String::new());
+                lines.push("Up and down to pick, left and right to change, Enter to go back".to_string());
+                draw_text(ctx, &mut canvas, lines.join("\n"), screen_width as f32 / 4.0, center_y);
+                return canvas.finish(ctx);
+            }
+            State::GameOver { won } => {
+                let title = if won { "You Win!" } else { "Game Over" };
+                let standings = self.get_standings().join("\n");
+                let text = format!("{}\nScore: {:.0}\n\nFinal standings:\n{}\n\nEnter to go back to the menu", title, score, standings);
+                draw_text(ctx, &mut canvas, text, screen_width as f32 / 4.0, screen_height as f32 / 4.0);
+                return canvas.finish(ctx);
+            }
+            State::Playing | State::Paused | State::Spectating => {}
         }
 
-        if self.game_over {
-            // Game over
-            let text = graphics::Text::new("Game Over");
-            let text_x = screen_width as f32 / 2.0;
-            let text_y = screen_height as f32 / 2.0;
-            let color = Color::from_rgb(0, 0, 0);
-            let text_pos = graphics::DrawParam::default().dest([text_x, text_y]).color(color);
-            canvas.draw(&text, text_pos);
+        if let Some(remaining) = self.world.get_respawn_time(&self.this_player) {
+            // Show the death screen until the player comes back
+            let stats = self.world.get_stats(&self.this_player);
+            let text = format!(
+                "You were eaten!\nRespawning in {:.0}...\n\nDeaths: {}\nCells eaten: {}\nFood eaten: {}\nPeak mass: {:.0}",
+                remaining.ceil(),
+                stats.deaths,
+                stats.cells_eaten,
+                stats.food_eaten,
+                stats.peak_mass.to_area() / Mass::default().to_area(),
+            );
+            draw_text(ctx, &mut canvas, text, center_x, center_y);
             return canvas.finish(ctx);
         }
-        
+
         // Draw code here...
-        let player = self.this_player;
-
-        // Screen size is 800x600
-        // Center of the screen is 400x300
-
-        let smallest_cell = self.world.get_player_cells(&player).into_iter().max_by(|a, b| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal)).unwrap();
-        let smallest_cell_radius = smallest_cell.get_mass().to_radius();
-        let default_cell_radius = Mass::default().to_radius();
-        
-        // let (camera_width, camera_height) = (smallest_cell_radius * 2.0 * 1.5, smallest_cell_radius * 2.0 * 1.5);
-        // let (camera_width, camera_height) = (screen_width / (smallest_cell_radius / default_cell_radius), screen_height / (smallest_cell_radius / default_cell_radius));
-        let (camera_width, camera_height) = (screen_width, screen_height);
-        
-        let (world_width, world_height) = (camera_width / (CAMERA_WIDTH * (smallest_cell_radius / default_cell_radius).sqrt()), camera_height / (CAMERA_HEIGHT * (smallest_cell_radius / default_cell_radius).sqrt()));
-
-        let camera_pos = self.world.get_camera_position(&player);
-        let (mut camera_x, mut camera_y) = camera_pos.project_onto(world_width, world_height);
-        camera_x -= camera_width / 2.0;
-        camera_y -= camera_height / 2.0;
-        // Draw all the entities
-        let entities = self.world.get_entities();
-
-
-        // Draw grid lines
-        let primary_color = Color::from_rgb(0, 0, 0);
-        let alt_color = Color::from_rgb(0, 0, 255);
-        
-        for y in 0..256 {
-            let color = if y % 16 == 0 {
-                alt_color
-            } else {
-                primary_color
-            };
-
-            // Draw horizontal line across world
-            let world_y = y as f64 / 256.0 * world_height;
-            let (x1, y1) = (0.0, world_y);
-            let (x2, y2) = (world_width, world_y);
-            let (x1, y1) = (x1 - camera_x, y1 - camera_y);
-            let (x2, y2) = (x2 - camera_x, y2 - camera_y);
-            let line = graphics::Mesh::new_line(ctx, &[[x1 as f32, y1 as f32], [x2 as f32, y2 as f32]], 1.0, color)?;
-            canvas.draw(&line, graphics::DrawParam::default());
-        }
-
-        for x in 0..256 {
-            let color = if x % 16 == 0 {
-                alt_color
-            } else {
-                primary_color
-            };
-
-            // Draw vertical line across world
-            let world_x = x as f64 / 256.0 * world_width;
-            let (x1, y1) = (world_x, 0.0);
-            let (x2, y2) = (world_x, world_height);
-            let (x1, y1) = (x1 - camera_x, y1 - camera_y);
-            let (x2, y2) = (x2 - camera_x, y2 - camera_y);
-            let line = graphics::Mesh::new_line(ctx, &[[x1 as f32, y1 as f32], [x2 as f32, y2 as f32]], 1.0, color)?;
-            canvas.draw(&line, graphics::DrawParam::default());
-        }
-
-        for (id, entity) in entities {
-            // eprintln!("id: {:?}", id);
-            // Draw the entity
-            // Get the position and radius
-
-            match entity {
-                Entity::Cell(cell) => {
-                    // Get the color
-                    let color: microbiome::Color = cell.get_player().unwrap().get_color();
-                    // eprintln!("color: {:?}", color);
-                    let ggez_color = Color::from_rgb(color.get_red(), color.get_green(), color.get_blue());
-
-                    // Get radius and position
-                    let radius = cell.get_mass().to_radius() * world_width / 2.0;
-                    let (x, y) = cell.get_position().project_onto(world_width, world_height);
-                    // let pos = cell.get_position();
-                    
-                    // println!("cell pos {}, {}", pos.0, pos.1);
-                    // println!("  radius {}", cell.get_mass().to_radius());
-
-                    // If out of bounds, don't draw
-                    if x < camera_x || x > camera_x + camera_width || y < camera_y || y > camera_y + camera_height {
-                        continue;
-                    }
-                    
-                    // Draw the player
-                    let circle = graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), [(x - camera_x) as f32, (y - camera_y) as f32], radius as f32, 0.1, ggez_color)?;
-                    canvas.draw(&circle, graphics::DrawParam::default());
-                },
-                Entity::Food(food) => {
-                    // Get the color
-                    let color = microbiome::Color::rgb(1.0, 0.0, 0.0);
-                    let ggez_color = Color::from_rgb(color.get_red(), color.get_green(), color.get_blue());
-
-                    let radius = food.to_mass().to_radius() * world_width / 2.0;
-                    let (x, y) = food.get_position().project_onto(world_width, world_height);
-                    // let pos = food.get_position();
-
-                    if x < camera_x || x > camera_x + camera_width || y < camera_y || y > camera_y + camera_height {
-                        continue;
-                    }
-                    // println!("food pos {}, {}", pos.0, pos.1);
-
-                    // Draw the food
-                    let circle = graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), [(x - camera_x) as f32, (y - camera_y) as f32], radius as f32, 0.1, ggez_color)?;
-                    // let circle = graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), [x as f32, y as f32], radius as f32, 0.1, ggez_color)?;
-                    canvas.draw(&circle, graphics::DrawParam::default());
-                },
-                Entity::Wall(wall) => {
-                    let color = microbiome::Color::rgb(0.0, 0.0, 0.0);
-                    let ggez_color = Color::from_rgb(color.get_red(), color.get_green(), color.get_blue());
-
-                    let radius = wall.get_radius() * world_width;
-                    let (x, y) = wall.get_position().project_onto(world_width, world_height);
-
-                    if x < camera_x || x > camera_x + camera_width || y < camera_y || y > camera_y + camera_height {
-                        continue;
-                    }
+        // While spectating someone else, the HUD and minimap are about them
+        let player = self.get_spectated().unwrap_or(self.this_player);
+        let view = self.camera.get_view(screen_width, screen_height);
+
+        let renderer = match &mut self.renderer {
+            Some(renderer) => renderer,
+            None => self.renderer.insert(Renderer::new(ctx)?),
+        };
+        renderer.draw_world(ctx, &mut canvas, &self.world, &view);
+        if self.show_decisions {
+            renderer.draw_decisions(ctx, &mut canvas, &self.world, &self.decisions, &view)?;
+        }
+
+        // Lines get thicker on HiDPI displays, so they're just as easy to see
+        let line_width = ctx.gfx.window().scale_factor() as f32;
+
+        // Draw the edge of the arena once it has shrunk
+        let bounds = self.world.get_bounds();
+        if bounds < 1.0 {
+            let [x1, y1] = view.project(Position(-bounds, -bounds));
+            let [x2, y2] = view.project(Position(bounds, bounds));
+            let rect = graphics::Rect::new(x1, y1, x2 - x1, y2 - y1);
+            let edge = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::stroke(4.0 * line_width), rect, Color::from_rgb(255, 0, 0))?;
+            canvas.draw(&edge, graphics::DrawParam::default());
+        }
+
+        // Draw the mode's zone, if it has one
+        if let Some((center, radius)) = self.mode.get_zone() {
+            let zone = graphics::Mesh::new_circle(ctx, graphics::DrawMode::stroke(4.0 * line_width), view.project(center), view.scale(radius), 0.1, Color::from_rgb(255, 200, 0))?;
+            canvas.draw(&zone, graphics::DrawParam::default());
+        }
+
+        let minimap = match &mut self.minimap {
+            Some(minimap) => minimap,
+            None => self.minimap.insert(Minimap::new(ctx, self.options.minimap_threshold)?),
+        };
+        minimap.draw(ctx, &mut canvas, &self.world, &player, &view)?;
+
+        // Show the mode, the score, and the time left
+        let mut status = format!("{}  Score: {:.0}", self.mode.get_name(), score);
+        if let Some(limit) = self.mode.get_time_limit() {
+            let remaining = (limit - self.mode.get_elapsed()).max(0.0) as u64;
+            status += &format!("  Time: {}:{:02}", remaining / 60, remaining % 60);
+        }
+        let mut hud = vec![status, String::new()];
+
+        // Show how the player is doing, and how the game is running
+        let cells = self.world.get_player_cells(&player);
+        let mass = cells.iter().map(|cell| cell.get_mass().to_area()).sum::<f64>() / Mass::default().to_area();
+        hud.push(format!("Mass: {:.0}", mass));
+        hud.push(format!("Cells: {}", cells.len()));
+        hud.push(match self.world.get_split_cooldown(&player) {
+            Some(cooldown) if cooldown > 0.0 => format!("Split: {:.1}s", cooldown),
+            _ => "Split: ready".to_string(),
+        });
+        hud.push(format!("Players: {}", self.world.get_players().len()));
+        if self.autopilot {
+            hud.push(match self.overridden {
+                overridden if overridden > 0.0 => format!("Autopilot: back in {:.1}s", overridden),
+                _ => "Autopilot: on".to_string(),
+            });
+        }
+        hud.push(format!("Tick: {}  Step: {:.4}s  FPS: {:.0}", self.world.tick, self.time_step, ctx.time.fps()));
+
+        if self.debug {
+            let (mut food, mut cells, mut walls) = (0, 0, 0);
+            for (_, entity) in self.world.get_entities() {
+                match entity {
+                    Entity::Food(_) => food += 1,
+                    Entity::Cell(_) => cells += 1,
+                    Entity::Wall(_) => walls += 1,
+                }
+            }
+            hud.push(String::new());
+            hud.push(format!("Food: {}  Cells: {}  Walls: {}", food, cells, walls));
+            hud.push(format!("Tick duration: {:.2}ms", self.tick_duration.as_secs_f64() * 1000.0));
+        }
 
-                    // Draw the virus
-                    let circle = graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), [(x - camera_x) as f32, (y - camera_y) as f32], radius as f32, 0.1, ggez_color)?;
-                    // let circle = graphics::Mesh::new_circle(ctx, graphics::DrawMode::fill(), [x as f32, y as f32], radius as f32, 0.1, ggez_color)?;
-                    canvas.draw(&circle, graphics::DrawParam::default());
-                },
+        let margin = 10.0 * ctx.gfx.window().scale_factor() as f32;
+        draw_text(ctx, &mut canvas, hud.join("\n"), margin, margin);
+
+        match self.state {
+            State::Paused => draw_text(ctx, &mut canvas, "Paused\n\nPress Escape to resume, or Enter to quit to the menu", center_x, center_y),
+            State::Spectating if self.eliminated => {
+                let watching = match self.get_spectated() {
+                    Some(player) if !self.camera.is_free() => player.get_name(),
+                    _ => "the dish".to_string(),
+                };
+                let text = format!("You were eaten! Watching {}\nSpace for the next player, C for the free camera", watching);
+                draw_text(ctx, &mut canvas, text, center_x, margin);
             }
+            State::Spectating => draw_text(ctx, &mut canvas, "Spectating - press C to take control again", center_x, margin),
+            _ => {}
         }
 
         // eprintln!("done drawing");
         canvas.finish(ctx)
     }
-}
\ No newline at end of file
+
+    // ggez quits whenever Escape is pressed unless this is overridden, but Escape pauses the game
+    // and backs out of menus here; quitting is done from the main menu instead
+    fn key_down_event(&mut self, _ctx: &mut Context, _input: KeyInput, _repeated: bool) -> GameResult {
+        Ok(())
+    }
+
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: f32, y: f32) -> GameResult {
+        self.camera.zoom_by(y as f64);
+        Ok(())
+    }
+}
+
+// Draw black text at a point on the screen, scaled up for HiDPI displays
+fn draw_text(ctx: &Context, canvas: &mut graphics::Canvas, text: impl Into<graphics::TextFragment>, x: f32, y: f32) {
+    let mut text = graphics::Text::new(text);
+    text.set_scale(TEXT_SIZE * ctx.gfx.window().scale_factor() as f32);
+    canvas.draw(&text, graphics::DrawParam::default().dest([x, y]).color(Color::from_rgb(0, 0, 0)));
+}
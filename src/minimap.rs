@@ -0,0 +1,97 @@
+use ggez::{Context, GameResult};
+use ggez::graphics::{Canvas, Color, DrawMode, DrawParam, InstanceArray, Mesh, Rect};
+use microbiome::*;
+use crate::render::{to_ggez_color, View};
+
+// The size of the minimap, as a fraction of the shorter side of the window
+const MINIMAP_SIZE: f64 = 1.0 / 5.0;
+
+// The smallest a dot on the minimap can be, in pixels without any HiDPI scaling
+const MIN_DOT_RADIUS: f32 = 1.5;
+
+// A map of the whole dish in the corner of the screen, showing where the player is
+// and where the big threats are
+pub struct Minimap {
+    circle: Mesh,
+    dots: InstanceArray,
+    // Only enemy cells at least this heavy are shown, or every enemy cell when this is None
+    threshold: Option<Mass>,
+    // The largest the arena has been, so that it can be seen shrinking
+    extent: f64,
+    visible: bool,
+}
+
+impl Minimap {
+    pub fn new(ctx: &Context, threshold: Option<Mass>) -> GameResult<Minimap> {
+        Ok(Minimap {
+            circle: Mesh::new_circle(ctx, DrawMode::fill(), [0.0, 0.0], 1.0, 0.01, Color::WHITE)?,
+            dots: InstanceArray::new(ctx, None),
+            threshold,
+            extent: 1.0,
+            visible: true,
+        })
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn draw(&mut self, ctx: &Context, canvas: &mut Canvas, world: &World, player: &Player, view: &View) -> GameResult {
+        if !self.visible {
+            return Ok(());
+        }
+
+        let scale_factor = ctx.gfx.window().scale_factor() as f32;
+        let size = view.screen_width.min(view.screen_height) * MINIMAP_SIZE;
+        let margin = 10.0 * scale_factor as f64;
+        let left = view.screen_width - size - margin;
+        let top = view.screen_height - size - margin;
+
+        self.extent = self.extent.max(world.get_bounds());
+        let extent = self.extent;
+        let to_map = |position: Position| -> [f32; 2] {
+            [
+                (left + (position.get_x() / extent + 1.0) * size / 2.0) as f32,
+                (top + (position.get_y() / extent + 1.0) * size / 2.0) as f32,
+            ]
+        };
+        let to_rect = |corner: Position, other: Position| -> Rect {
+            let [x1, y1] = to_map(corner);
+            let [x2, y2] = to_map(other);
+            Rect::new(x1, y1, x2 - x1, y2 - y1)
+        };
+
+        let background = Mesh::new_rectangle(ctx, DrawMode::fill(), Rect::new(left as f32, top as f32, size as f32, size as f32), Color::new(0.9, 0.9, 0.9, 0.8))?;
+        canvas.draw(&background, DrawParam::default());
+
+        let bounds = world.get_bounds();
+        let arena = Mesh::new_rectangle(ctx, DrawMode::stroke(scale_factor), to_rect(Position(-bounds, -bounds), Position(bounds, bounds)), Color::BLACK)?;
+        canvas.draw(&arena, DrawParam::default());
+
+        // Enemies are drawn first, so the player's own cells are never hidden
+        let min_radius = MIN_DOT_RADIUS * scale_factor;
+        let dot = |position: Position, radius: f64, color: Color| {
+            let r = ((radius / extent * size / 2.0) as f32).max(min_radius);
+            DrawParam::default().dest(to_map(position)).scale([r, r]).color(color)
+        };
+        let (mine, enemies): (Vec<&Cell>, Vec<&Cell>) = world.get_cells().into_iter()
+            .filter(|cell| cell.get_player().is_some_and(|owner| owner.get_id() == player.get_id() || !owner.is_ally_of(player)))
+            .partition(|cell| cell.get_player_id() == Some(player.get_id()));
+        self.dots.set(
+            enemies.into_iter()
+                .filter(|cell| self.threshold.is_none_or(|threshold| cell.get_mass() >= threshold))
+                .chain(mine)
+                .map(|cell| {
+                    let color = cell.get_player().map(|owner| to_ggez_color(owner.get_color())).unwrap_or(Color::BLACK);
+                    dot(cell.get_position(), cell.get_radius(), color)
+                })
+        );
+        canvas.draw_instanced_mesh(self.circle.clone(), &self.dots, DrawParam::default());
+
+        // Outline the part of the dish that's on the screen
+        let viewport = to_rect(view.unproject(0.0, 0.0), view.unproject(view.screen_width, view.screen_height));
+        let viewport = Mesh::new_rectangle(ctx, DrawMode::stroke(scale_factor), viewport, Color::from_rgb(0, 0, 255))?;
+        canvas.draw(&viewport, DrawParam::default());
+        Ok(())
+    }
+}
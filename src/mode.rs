@@ -0,0 +1,345 @@
+use crate::*;
+use serde_derive::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+// How a match is going for a given player
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Outcome {
+    Undecided,
+    Won,
+    Lost,
+}
+
+// How players are placed into the world at the start of a match
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpawnRules {
+    // The number of bots to fight against
+    pub bots: usize,
+    // The number of cells each player starts with
+    pub starting_cells: usize,
+    // The mass of each of the player's starting cells
+    pub starting_mass: Mass,
+    // How many seconds an eliminated player waits to respawn, or None to stay eliminated
+    pub respawn_delay: Option<f64>,
+}
+
+impl Default for SpawnRules {
+    fn default() -> Self {
+        SpawnRules {
+            bots: 10,
+            starting_cells: 10,
+            starting_mass: Mass::default() * 50.0,
+            respawn_delay: None,
+        }
+    }
+}
+
+// The rules of a match: how it starts, how it's won, and how it's scored
+pub trait GameMode {
+    // The name shown on the mode selection screen
+    fn get_name(&self) -> &str;
+
+    // A short explanation of how to win
+    fn get_description(&self) -> &str;
+
+    fn get_spawn_rules(&self) -> SpawnRules {
+        SpawnRules::default()
+    }
+
+    // The length of the match in seconds, if it has one
+    fn get_time_limit(&self) -> Option<f64> {
+        None
+    }
+
+    // The number of seconds the match has been going on
+    fn get_elapsed(&self) -> f64;
+
+    // A circle that the mode cares about, like the hill in King of the Hill
+    fn get_zone(&self) -> Option<(Position, f64)> {
+        None
+    }
+
+    // Advance the mode's own state by a number of real seconds
+    fn tick(&mut self, world: &mut World, seconds_since_last_tick: f64);
+
+    fn get_score(&self, world: &World, player: &Player) -> f64;
+
+    fn get_outcome(&self, world: &World, player: &Player) -> Outcome;
+}
+
+// Get every built-in game mode, in the order they should be offered
+pub fn builtin_modes() -> Vec<Box<dyn GameMode>> {
+    vec![
+        Box::new(FreeForAll::new()),
+        Box::new(Timed::new(5.0 * 60.0)),
+        Box::new(Survival::new(5.0 * 60.0, 0.25)),
+        Box::new(KingOfTheHill::new(Position(0.0, 0.0), 0.15, 60.0)),
+    ]
+}
+
+// The rules everyone shares: you lose when all your cells are eaten for good,
+// and you win when only you and your teammates are left
+fn last_team_standing(world: &World, player: &Player) -> Outcome {
+    if world.get_player_cells(player).is_empty() && !world.is_respawning(player) {
+        Outcome::Lost
+    } else if world.is_last_team_standing(player) {
+        Outcome::Won
+    } else {
+        Outcome::Undecided
+    }
+}
+
+// Eat everyone else to win
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FreeForAll {
+    elapsed: f64,
+}
+
+impl FreeForAll {
+    pub fn new() -> FreeForAll {
+        FreeForAll { elapsed: 0.0 }
+    }
+}
+
+impl GameMode for FreeForAll {
+    fn get_name(&self) -> &str {
+        "Free-For-All"
+    }
+
+    fn get_description(&self) -> &str {
+        "Eat every other microbe in the dish"
+    }
+
+    fn get_elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    fn tick(&mut self, _world: &mut World, seconds_since_last_tick: f64) {
+        self.elapsed += seconds_since_last_tick;
+    }
+
+    fn get_score(&self, world: &World, player: &Player) -> f64 {
+        world.get_player_mass(player).to_area()
+    }
+
+    fn get_outcome(&self, world: &World, player: &Player) -> Outcome {
+        last_team_standing(world, player)
+    }
+}
+
+// Be the biggest microbe when the clock runs out
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Timed {
+    duration: f64,
+    elapsed: f64,
+}
+
+impl Timed {
+    pub fn new(duration: f64) -> Timed {
+        Timed {
+            duration,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl GameMode for Timed {
+    fn get_name(&self) -> &str {
+        "Timed"
+    }
+
+    fn get_description(&self) -> &str {
+        "Have the most mass when time runs out"
+    }
+
+    // Nobody is knocked out of a timed match for good
+    fn get_spawn_rules(&self) -> SpawnRules {
+        SpawnRules {
+            respawn_delay: Some(5.0),
+            ..SpawnRules::default()
+        }
+    }
+
+    fn get_time_limit(&self) -> Option<f64> {
+        Some(self.duration)
+    }
+
+    fn get_elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    fn tick(&mut self, _world: &mut World, seconds_since_last_tick: f64) {
+        self.elapsed = (self.elapsed + seconds_since_last_tick).min(self.duration);
+    }
+
+    fn get_score(&self, world: &World, player: &Player) -> f64 {
+        world.get_player_mass(player).to_area()
+    }
+
+    fn get_outcome(&self, world: &World, player: &Player) -> Outcome {
+        let outcome = last_team_standing(world, player);
+        if outcome != Outcome::Undecided || self.elapsed < self.duration {
+            return outcome;
+        }
+
+        let my_score = self.get_score(world, player);
+        if world.get_players().into_iter().filter(|other| !other.is_ally_of(player)).all(|other| self.get_score(world, other) <= my_score) {
+            Outcome::Won
+        } else {
+            Outcome::Lost
+        }
+    }
+}
+
+// Outlast everyone while the walls of the dish close in
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Survival {
+    // How long it takes the arena to shrink all the way
+    duration: f64,
+    // The size the arena shrinks down to
+    min_bounds: f64,
+    elapsed: f64,
+}
+
+impl Survival {
+    pub fn new(duration: f64, min_bounds: f64) -> Survival {
+        Survival {
+            duration,
+            min_bounds,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl GameMode for Survival {
+    fn get_name(&self) -> &str {
+        "Survival"
+    }
+
+    fn get_description(&self) -> &str {
+        "Be the last one standing as the dish shrinks"
+    }
+
+    fn get_elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    fn tick(&mut self, world: &mut World, seconds_since_last_tick: f64) {
+        self.elapsed += seconds_since_last_tick;
+        let progress = (self.elapsed / self.duration).min(1.0);
+        world.set_bounds(1.0 - (1.0 - self.min_bounds) * progress);
+
+        // Food outside of the arena can never be eaten
+        let bounds = world.get_bounds();
+        let outside = world.get_entities().into_iter().filter_map(|(id, entity)| match entity {
+            Entity::Food(food) if food.get_position().get_x().abs() > bounds || food.get_position().get_y().abs() > bounds => Some(id),
+            _ => None,
+        }).collect::<Vec<_>>();
+        for id in outside {
+            world.remove_entity(id);
+        }
+    }
+
+    fn get_score(&self, _world: &World, _player: &Player) -> f64 {
+        self.elapsed
+    }
+
+    fn get_outcome(&self, world: &World, player: &Player) -> Outcome {
+        last_team_standing(world, player)
+    }
+}
+
+// Hold the zone in the middle of the dish for long enough to win
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KingOfTheHill {
+    center: Position,
+    radius: f64,
+    // How many seconds a player must hold the zone to win
+    target: f64,
+    // How many seconds each player has held the zone
+    held: HashMap<ID, f64>,
+    elapsed: f64,
+}
+
+impl KingOfTheHill {
+    pub fn new(center: Position, radius: f64, target: f64) -> KingOfTheHill {
+        KingOfTheHill {
+            center,
+            radius,
+            target,
+            held: HashMap::new(),
+            elapsed: 0.0,
+        }
+    }
+
+    // Get the player with the most mass inside of the zone
+    pub fn get_king(&self, world: &World) -> Option<ID> {
+        let mut mass_in_zone = HashMap::new();
+        for cell in world.get_cells() {
+            if cell.get_position().distance_to(self.center) < self.radius {
+                if let Some(id) = cell.get_player_id() {
+                    *mass_in_zone.entry(id).or_insert(0.0) += cell.get_mass().to_area();
+                }
+            }
+        }
+
+        mass_in_zone.into_iter()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(id, _)| id)
+    }
+
+    pub fn get_held(&self, player: &Player) -> f64 {
+        self.held.get(&player.get_id()).copied().unwrap_or(0.0)
+    }
+}
+
+impl GameMode for KingOfTheHill {
+    fn get_name(&self) -> &str {
+        "King of the Hill"
+    }
+
+    fn get_description(&self) -> &str {
+        "Hold the zone in the middle of the dish"
+    }
+
+    fn get_elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    fn get_spawn_rules(&self) -> SpawnRules {
+        SpawnRules {
+            respawn_delay: Some(5.0),
+            ..SpawnRules::default()
+        }
+    }
+
+    fn get_zone(&self) -> Option<(Position, f64)> {
+        Some((self.center, self.radius))
+    }
+
+    fn tick(&mut self, world: &mut World, seconds_since_last_tick: f64) {
+        self.elapsed += seconds_since_last_tick;
+        if let Some(king) = self.get_king(world) {
+            *self.held.entry(king).or_insert(0.0) += seconds_since_last_tick;
+        }
+    }
+
+    fn get_score(&self, _world: &World, player: &Player) -> f64 {
+        self.get_held(player)
+    }
+
+    fn get_outcome(&self, world: &World, player: &Player) -> Outcome {
+        let outcome = last_team_standing(world, player);
+        if outcome != Outcome::Undecided {
+            return outcome;
+        }
+
+        // Whoever holds the zone long enough wins it for their whole team
+        for other in world.get_players() {
+            if self.get_held(other) >= self.target {
+                return if other.is_ally_of(player) { Outcome::Won } else { Outcome::Lost };
+            }
+        }
+        Outcome::Undecided
+    }
+}
@@ -0,0 +1,325 @@
+use ggez::{Context, GameResult};
+use ggez::graphics::{self, Canvas, Color, DrawParam, Image, InstanceArray, Mesh, Text, TextLayout};
+use microbiome::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use microbiome::ai::{Decision, Motive};
+
+// The number of grid lines across the whole world, and how often a line is highlighted
+const GRID_LINES: usize = 256;
+const GRID_HIGHLIGHT: usize = 16;
+
+// How thick a cell's outline is, as a fraction of its radius
+const OUTLINE: f64 = 0.08;
+
+// How much a cell's membrane stretches while it moves at the default speed, as a fraction
+// of its radius, the most it can stretch, and how fast it wobbles in radians per second
+const WOBBLE: f64 = 0.02;
+const MAX_WOBBLE: f64 = 0.08;
+const WOBBLE_RATE: f64 = 6.0;
+
+// Cells smaller than this on the screen don't get a mass label, in pixels without any HiDPI scaling
+const LABEL_RADIUS: f32 = 16.0;
+
+// The length of the AI's strongest reason to move, and of the direction it picks,
+// in pixels without any HiDPI scaling
+const ARROW_LENGTH: f32 = 60.0;
+
+// How the world is laid out on the screen
+#[derive(Clone, Copy, Debug)]
+pub struct View {
+    // The number of pixels that the whole world (from -1 to 1) spans
+    pub world_size: f64,
+    // The pixel coordinates of the top-left corner of the screen, relative to the world's corner
+    pub camera_x: f64,
+    pub camera_y: f64,
+    pub screen_width: f64,
+    pub screen_height: f64,
+}
+
+impl View {
+    // Center the screen on a position in the world
+    pub fn new(center: Position, world_size: f64, screen_width: f64, screen_height: f64) -> View {
+        let (x, y) = center.project_onto(world_size, world_size);
+        View {
+            world_size,
+            camera_x: x - screen_width / 2.0,
+            camera_y: y - screen_height / 2.0,
+            screen_width,
+            screen_height,
+        }
+    }
+
+    // Get the pixel coordinates of a position in the world
+    pub fn project(&self, position: Position) -> [f32; 2] {
+        let (x, y) = position.project_onto(self.world_size, self.world_size);
+        [(x - self.camera_x) as f32, (y - self.camera_y) as f32]
+    }
+
+    // Get the position in the world under a pixel on the screen
+    pub fn unproject(&self, x: f64, y: f64) -> Position {
+        Position((x + self.camera_x) * 2.0 / self.world_size - 1.0, (y + self.camera_y) * 2.0 / self.world_size - 1.0)
+    }
+
+    // Get the number of pixels a distance in the world takes up
+    pub fn scale(&self, distance: f64) -> f32 {
+        (distance * self.world_size / 2.0) as f32
+    }
+
+    // Is any part of a circle in the world visible on the screen?
+    pub fn is_visible(&self, position: Position, radius: f64) -> bool {
+        let [x, y] = self.project(position);
+        let r = self.scale(radius);
+        x + r >= 0.0 && x - r <= self.screen_width as f32 && y + r >= 0.0 && y - r <= self.screen_height as f32
+    }
+}
+
+pub fn to_ggez_color(color: microbiome::Color) -> Color {
+    Color::from_rgb(color.get_red(), color.get_green(), color.get_blue())
+}
+
+// Draws the world in a handful of draw calls: every circle is the same
+// unit circle mesh, scaled and colored per instance.
+pub struct Renderer {
+    circle: Mesh,
+    square: Mesh,
+    food: InstanceArray,
+    cells: InstanceArray,
+    walls: InstanceArray,
+    grid: InstanceArray,
+    // The skin for each player name, drawn in one batch per skin.
+    // Names without a skin in `assets/skins` are remembered as None.
+    skins: HashMap<String, Option<InstanceArray>>,
+    mass_labels: bool,
+}
+
+impl Renderer {
+    pub fn new(ctx: &Context) -> GameResult<Renderer> {
+        // The tolerance is small so that the circle stays smooth when it's scaled up
+        let circle = Mesh::new_circle(ctx, graphics::DrawMode::fill(), [0.0, 0.0], 1.0, 0.0005, Color::WHITE)?;
+        let square = Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), graphics::Rect::new(0.0, 0.0, 1.0, 1.0), Color::WHITE)?;
+        Ok(Renderer {
+            circle,
+            square,
+            food: InstanceArray::new(ctx, None),
+            cells: InstanceArray::new(ctx, None),
+            walls: InstanceArray::new(ctx, None),
+            grid: InstanceArray::new(ctx, None),
+            skins: HashMap::new(),
+            mass_labels: false,
+        })
+    }
+
+    pub fn toggle_mass_labels(&mut self) {
+        self.mass_labels = !self.mass_labels;
+    }
+
+    pub fn draw_world(&mut self, ctx: &Context, canvas: &mut Canvas, world: &World, view: &View) {
+        self.draw_grid(ctx, canvas, view);
+
+        self.food.clear();
+        self.walls.clear();
+        let mut cells = Vec::new();
+        for (id, entity) in world.get_entities() {
+            match entity {
+                Entity::Food(food) => {
+                    if view.is_visible(food.get_position(), food.to_radius()) {
+                        self.food.push(circle_instance(view, food.get_position(), food.to_radius(), Color::from_rgb(255, 0, 0)));
+                    }
+                }
+                Entity::Cell(cell) => {
+                    if view.is_visible(cell.get_position(), cell.get_radius()) {
+                        cells.push((id, cell));
+                    }
+                }
+                Entity::Wall(wall) => {
+                    if view.is_visible(wall.get_position(), wall.get_radius()) {
+                        self.walls.push(circle_instance(view, wall.get_position(), wall.get_radius(), Color::BLACK));
+                    }
+                }
+            }
+        }
+
+        // Bigger cells are drawn on top of smaller ones. Each cell is a darker outline
+        // with its fill on top, both in the same batch so the order is kept.
+        cells.sort_by(|(_, a), (_, b)| a.get_mass().partial_cmp(&b.get_mass()).unwrap_or(std::cmp::Ordering::Equal));
+        let time = ctx.time.time_since_start().as_secs_f64();
+        let min_outline = ctx.gfx.window().scale_factor();
+        let mut instances = Vec::with_capacity(cells.len() * 2);
+        for (id, cell) in &cells {
+            let color = cell.get_player().map(|player| player.get_color()).unwrap_or(microbiome::Color { r: 0, g: 0, b: 0 });
+
+            // The membrane stretches along the way the cell is moving, faster cells more so
+            let stretch = (cell.get_speed().0.abs() / Speed::default().0 * WOBBLE).min(MAX_WOBBLE) * (time * WOBBLE_RATE + phase(*id)).sin();
+            let r = view.scale(cell.get_radius()) as f64;
+            let outline = (r * OUTLINE).max(min_outline);
+            let rotation = cell.get_direction().to_radians() as f32;
+            let membrane = |radius: f64, color: Color| {
+                DrawParam::default()
+                    .dest(view.project(cell.get_position()))
+                    .rotation(rotation)
+                    .scale([(radius * (1.0 + stretch)) as f32, (radius * (1.0 - stretch)) as f32])
+                    .color(color)
+            };
+            instances.push(membrane(r, to_ggez_color(color.darken(0.3))));
+            instances.push(membrane((r - outline).max(0.0), to_ggez_color(color)));
+        }
+        self.cells.set(instances);
+
+        canvas.draw_instanced_mesh(self.circle.clone(), &self.food, DrawParam::default());
+        canvas.draw_instanced_mesh(self.circle.clone(), &self.cells, DrawParam::default());
+        canvas.draw_instanced_mesh(self.circle.clone(), &self.walls, DrawParam::default());
+
+        self.draw_skins(ctx, canvas, &cells, view);
+        if self.mass_labels {
+            draw_mass_labels(ctx, canvas, &cells, view);
+        }
+    }
+
+    // Draw the skins in `assets/skins` over the cells of players with the same name
+    fn draw_skins(&mut self, ctx: &Context, canvas: &mut Canvas, cells: &[(ID, &Cell)], view: &View) {
+        for skin in self.skins.values_mut().flatten() {
+            skin.clear();
+        }
+
+        for (_, cell) in cells {
+            let name = match cell.get_player() {
+                Some(player) => player.get_name(),
+                None => continue,
+            };
+            let skin = self.skins.entry(name).or_insert_with_key(|name| {
+                Image::from_path(ctx, format!("/skins/{}.png", name)).ok().map(|image| InstanceArray::new(ctx, image))
+            });
+            if let Some(skin) = skin {
+                let r = view.scale(cell.get_radius());
+                let [x, y] = view.project(cell.get_position());
+                let image = skin.image();
+                skin.push(DrawParam::default().dest([x - r, y - r]).scale([2.0 * r / image.width() as f32, 2.0 * r / image.height() as f32]));
+            }
+        }
+
+        for skin in self.skins.values().flatten() {
+            canvas.draw(skin, DrawParam::default());
+        }
+    }
+
+    // Draw the grid lines that are on screen, as stretched out squares
+    fn draw_grid(&mut self, ctx: &Context, canvas: &mut Canvas, view: &View) {
+        // Lines get thicker on HiDPI displays, so they're just as easy to see
+        let line_width = ctx.gfx.window().scale_factor() as f32;
+        let spacing = view.world_size / GRID_LINES as f64;
+        let length = view.world_size as f32;
+
+        self.grid.clear();
+        for i in 0..GRID_LINES {
+            let color = if i % GRID_HIGHLIGHT == 0 {
+                Color::from_rgb(0, 0, 255)
+            } else {
+                Color::from_rgb(0, 0, 0)
+            };
+
+            let offset = i as f64 * spacing;
+            let y = (offset - view.camera_y) as f32;
+            if y >= 0.0 && y <= view.screen_height as f32 {
+                self.grid.push(DrawParam::default().dest([-view.camera_x as f32, y]).scale([length, line_width]).color(color));
+            }
+
+            let x = (offset - view.camera_x) as f32;
+            if x >= 0.0 && x <= view.screen_width as f32 {
+                self.grid.push(DrawParam::default().dest([x, -view.camera_y as f32]).scale([line_width, length]).color(color));
+            }
+        }
+        canvas.draw_instanced_mesh(self.square.clone(), &self.grid, DrawParam::default());
+    }
+}
+
+impl Renderer {
+    // Draw every reason each bot had for moving as an arrow, scaled by how much it mattered,
+    // along with the direction the bot picked and a ring around bots that are splitting
+    pub fn draw_decisions(&mut self, ctx: &Context, canvas: &mut Canvas, world: &World, decisions: &[Decision], view: &View) -> GameResult {
+        let scale_factor = ctx.gfx.window().scale_factor() as f32;
+        let mut builder = graphics::MeshBuilder::new();
+        let mut empty = true;
+        for decision in decisions {
+            let center = world.get_camera_position(&decision.player);
+            if !view.is_visible(center, 0.0) {
+                continue;
+            }
+
+            // Profiles can give some things no weight at all, and there's nothing to draw for those
+            let contributions = decision.contributions.iter().filter(|contribution| contribution.weight != 0.0 && contribution.weight.is_finite());
+            let strongest = contributions.clone().map(|contribution| contribution.weight.abs()).fold(0.0, f64::max);
+            for contribution in contributions {
+                let length = ARROW_LENGTH * scale_factor * (contribution.weight.abs() / strongest) as f32;
+                if length < scale_factor * 2.0 || !view.is_visible(contribution.origin, 0.0) {
+                    continue;
+                }
+                let color = match contribution.motive {
+                    Motive::Food => Color::from_rgb(0, 160, 0),
+                    Motive::Prey => Color::from_rgb(0, 0, 255),
+                    Motive::Threat => Color::from_rgb(255, 0, 0),
+                    Motive::Attack => Color::from_rgb(255, 140, 0),
+                    Motive::Escape => Color::from_rgb(0, 200, 200),
+                };
+                let sign = contribution.weight.signum();
+                let (x, y) = (sign * contribution.direction.x_component(), sign * contribution.direction.y_component());
+                arrow(&mut builder, view.project(contribution.origin), [x as f32, y as f32], length, scale_factor, color)?;
+            }
+
+            let (x, y) = (decision.direction.x_component(), decision.direction.y_component());
+            arrow(&mut builder, view.project(center), [x as f32, y as f32], ARROW_LENGTH * 1.5 * scale_factor, 3.0 * scale_factor, Color::BLACK)?;
+            if decision.split {
+                builder.circle(graphics::DrawMode::stroke(2.0 * scale_factor), view.project(center), 12.0 * scale_factor, 0.5, Color::from_rgb(255, 0, 255))?;
+            }
+            empty = false;
+        }
+
+        if !empty {
+            canvas.draw(&Mesh::from_data(ctx, builder.build()), DrawParam::default());
+        }
+        Ok(())
+    }
+}
+
+// Label each cell that's big enough on the screen with its mass
+fn draw_mass_labels(ctx: &Context, canvas: &mut Canvas, cells: &[(ID, &Cell)], view: &View) {
+    let scale_factor = ctx.gfx.window().scale_factor() as f32;
+    for (_, cell) in cells {
+        let r = view.scale(cell.get_radius());
+        if r < LABEL_RADIUS * scale_factor {
+            continue;
+        }
+
+        let mut text = Text::new(format!("{:.0}", cell.get_mass().to_area() / Mass::default().to_area()));
+        text.set_scale(r / 2.0).set_layout(TextLayout::center());
+        canvas.draw(&text, DrawParam::default().dest(view.project(cell.get_position())).color(Color::WHITE));
+    }
+}
+
+// Every cell wobbles a little out of step with the others
+fn phase(id: ID) -> f64 {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    (hasher.finish() % 1000) as f64 / 1000.0 * std::f64::consts::TAU
+}
+
+// Add an arrow pointing from a point in a direction on the screen
+fn arrow(builder: &mut graphics::MeshBuilder, from: [f32; 2], [x, y]: [f32; 2], length: f32, width: f32, color: Color) -> GameResult {
+    let to = [from[0] + x * length, from[1] + y * length];
+    builder.line(&[from, to], width, color)?;
+
+    let head = 3.0 * width;
+    let back = [to[0] - x * head, to[1] - y * head];
+    builder.polygon(graphics::DrawMode::fill(), &[
+        to,
+        [back[0] - y * head / 2.0, back[1] + x * head / 2.0],
+        [back[0] + y * head / 2.0, back[1] - x * head / 2.0],
+    ], color)?;
+    Ok(())
+}
+
+fn circle_instance(view: &View, position: Position, radius: f64, color: Color) -> DrawParam {
+    let r = view.scale(radius);
+    DrawParam::default().dest(view.project(position)).scale([r, r]).color(color)
+}
@@ -0,0 +1,394 @@
+use crate::*;
+use crate::ai::Profile;
+use rand::distributions::uniform::SampleUniform;
+use serde_derive::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fmt;
+
+// A description of how a world starts out, which can be loaded from a JSON file.
+// Masses are given in multiples of the default mass, and positions are in world
+// coordinates. Anything without a position is placed with `World::find_spawn_position`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    // Half the width of the square arena
+    #[serde(default = "default_bounds")]
+    pub bounds: f64,
+    // The world's settings; when this is missing, the game mode decides
+    #[serde(default)]
+    pub config: Option<WorldConfig>,
+    #[serde(default)]
+    pub teams: Vec<TeamSpec>,
+    pub players: Vec<PlayerSpec>,
+    #[serde(default)]
+    pub food: Vec<FoodSpec>,
+    #[serde(default)]
+    pub walls: Vec<Wall>,
+}
+
+fn default_bounds() -> f64 {
+    1.0
+}
+
+fn default_count() -> usize {
+    1
+}
+
+// Either an exact value, or a range to pick a random value from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Range<T> {
+    Exact(T),
+    Between(T, T),
+}
+
+impl<T: SampleUniform + PartialOrd + Copy> Range<T> {
+    pub fn sample(&self) -> T {
+        match self {
+            Range::Exact(value) => *value,
+            Range::Between(low, high) => with_rng(|rng| rng.gen_range(*low..=*high)),
+        }
+    }
+
+    // Check that the range can be sampled: the low end can't be above the high end, and nothing can be NaN
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Range::Exact(value) => value.partial_cmp(value).is_some(),
+            Range::Between(low, high) => low <= high,
+        }
+    }
+
+    pub fn get_low(&self) -> T {
+        match self {
+            Range::Exact(value) => *value,
+            Range::Between(low, _) => *low,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TeamSpec {
+    // The name players use to join this team
+    pub name: String,
+    // A random color is picked when this is missing
+    #[serde(default)]
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub friendly_fire: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSpec {
+    pub name: String,
+    // How many players to create from this description
+    #[serde(default = "default_count")]
+    pub count: usize,
+    // Whether this is the player controlled by the keyboard and mouse
+    #[serde(default)]
+    pub human: bool,
+    // A random color is picked when this is missing
+    #[serde(default)]
+    pub color: Option<Color>,
+    // The name of the team to join
+    #[serde(default)]
+    pub team: Option<String>,
+    // Where the player's cells are gathered
+    #[serde(default)]
+    pub position: Option<Position>,
+    // How the AI plays for this player; a random built-in profile is picked when this is missing
+    #[serde(default)]
+    pub profile: Option<ProfileSpec>,
+    pub cells: CellSpec,
+}
+
+// Either the name of a built-in AI profile, or a whole profile
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProfileSpec {
+    Named(String),
+    Custom(Profile),
+}
+
+impl ProfileSpec {
+    pub fn resolve(&self) -> Option<Profile> {
+        match self {
+            ProfileSpec::Named(name) => Profile::find(name),
+            ProfileSpec::Custom(profile) => Some(profile.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CellSpec {
+    pub count: Range<usize>,
+    pub mass: Range<f64>,
+    // How far each cell can be from the player's position
+    pub spread: Range<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FoodSpec {
+    // The amount of food, or the amount of food in each cluster
+    pub count: usize,
+    pub mass: Range<f64>,
+    // Gather the food into this many clusters instead of scattering it
+    #[serde(default)]
+    pub clusters: Option<usize>,
+    // How far each food can be from the center of its cluster
+    #[serde(default)]
+    pub spread: f64,
+    // The center of every cluster; random when this is missing
+    #[serde(default)]
+    pub position: Option<Position>,
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    UnknownProfile(String),
+    UnknownTeam(String),
+    // A range that can't be picked from, and what it's for
+    InvalidRange(String),
+    // An arena that isn't a positive size
+    InvalidBounds(f64),
+    // How many human players there are, when there isn't exactly one
+    HumanCount(usize),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioError::Io(err) => write!(f, "could not read scenario: {}", err),
+            ScenarioError::Json(err) => write!(f, "could not parse scenario: {}", err),
+            ScenarioError::UnknownProfile(name) => write!(f, "unknown AI profile {:?}", name),
+            ScenarioError::UnknownTeam(name) => write!(f, "unknown team {:?}", name),
+            ScenarioError::InvalidRange(what) => write!(f, "the range for {} is backwards or not a number", what),
+            ScenarioError::InvalidBounds(bounds) => write!(f, "the bounds have to be a number over 0, not {}", bounds),
+            ScenarioError::HumanCount(count) => write!(f, "there has to be exactly one human player, not {}", count),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<std::io::Error> for ScenarioError {
+    fn from(err: std::io::Error) -> Self {
+        ScenarioError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for ScenarioError {
+    fn from(err: serde_json::Error) -> Self {
+        ScenarioError::Json(err)
+    }
+}
+
+// Turn a string into a fixed-size player name, padding it with spaces
+pub fn to_name(name: &str) -> [char; 32] {
+    let mut result = [' '; 32];
+    for (slot, c) in result.iter_mut().zip(name.chars()) {
+        *slot = c;
+    }
+    result
+}
+
+impl Scenario {
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Scenario, ScenarioError> {
+        let json = std::fs::read_to_string(path)?;
+        Scenario::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Scenario, ScenarioError> {
+        let scenario: Scenario = serde_json::from_str(json)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    // Check for anything that would stop the scenario from being built
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        if !(self.bounds.is_finite() && self.bounds > 0.0) {
+            return Err(ScenarioError::InvalidBounds(self.bounds));
+        }
+        let humans = self.players.iter().filter(|spec| spec.human).map(|spec| spec.count).sum::<usize>();
+        if humans != 1 {
+            return Err(ScenarioError::HumanCount(humans));
+        }
+        for spec in &self.players {
+            if let Some(ProfileSpec::Named(name)) = &spec.profile {
+                if Profile::find(name).is_none() {
+                    return Err(ScenarioError::UnknownProfile(name.clone()));
+                }
+            }
+            if let Some(team) = &spec.team {
+                if !self.teams.iter().any(|other| &other.name == team) {
+                    return Err(ScenarioError::UnknownTeam(team.clone()));
+                }
+            }
+            let cells = &spec.cells;
+            for (valid, what) in [(cells.count.is_valid(), "cell count"), (cells.mass.is_valid(), "cell mass"), (cells.spread.is_valid(), "cell spread")] {
+                if !valid {
+                    return Err(ScenarioError::InvalidRange(format!("the {} of {:?}", what, spec.name)));
+                }
+            }
+        }
+        for (i, spec) in self.food.iter().enumerate() {
+            if !spec.mass.is_valid() {
+                return Err(ScenarioError::InvalidRange(format!("the mass of food #{}", i + 1)));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("scenarios can always be serialized")
+    }
+
+    // The standard petri dish: the player against a number of bots, with plenty of food
+    pub fn from_spawn_rules(rules: &SpawnRules) -> Scenario {
+        Scenario {
+            name: "Default".to_string(),
+            bounds: 1.0,
+            config: None,
+            teams: Vec::new(),
+            players: vec![
+                PlayerSpec {
+                    name: "Player".to_string(),
+                    count: 1,
+                    human: true,
+                    color: None,
+                    team: None,
+                    position: None,
+                    profile: None,
+                    cells: CellSpec {
+                        count: Range::Exact(rules.starting_cells),
+                        mass: Range::Exact(rules.starting_mass.0 / Mass::default().0),
+                        spread: Range::Exact(0.1),
+                    },
+                },
+                PlayerSpec {
+                    name: "Bot".to_string(),
+                    count: rules.bots,
+                    human: false,
+                    color: None,
+                    team: None,
+                    position: None,
+                    profile: None,
+                    cells: CellSpec {
+                        count: Range::Between(2, 9),
+                        mass: Range::Exact(500.0),
+                        spread: Range::Between(0.05, 0.1),
+                    },
+                },
+            ],
+            food: vec![
+                FoodSpec {
+                    count: 2000,
+                    mass: Range::Between(1.0, 3.0),
+                    clusters: None,
+                    spread: 0.0,
+                    position: None,
+                },
+                FoodSpec {
+                    count: 50,
+                    mass: Range::Between(1.0, 3.0),
+                    clusters: Some(20),
+                    spread: 0.05,
+                    position: None,
+                },
+            ],
+            walls: Vec::new(),
+        }
+    }
+
+    // Build the world this scenario describes, along with the human player if there is one,
+    // and the AI profile of every player that the scenario picked one for
+    pub fn build(&self) -> (World, Option<Player>, HashMap<ID, Profile>) {
+        let mut world = World::new();
+        world.set_bounds(self.bounds);
+        if let Some(config) = self.config {
+            world.config = config;
+        }
+
+        for wall in &self.walls {
+            world.add_entity(Entity::Wall(*wall));
+        }
+
+        let teams = self.teams.iter().map(|spec| {
+            let team = world.create_new_team(spec.color.unwrap_or_else(Color::random), spec.friendly_fire);
+            (spec.name.as_str(), team)
+        }).collect::<HashMap<_, _>>();
+
+        // The human goes last, so that they spawn away from everyone else
+        let mut human = None;
+        let mut profiles = HashMap::new();
+        for spec in self.players.iter().filter(|spec| !spec.human).chain(self.players.iter().filter(|spec| spec.human)) {
+            for _ in 0..spec.count {
+                let mut player = world.create_empty_player(to_name(&spec.name), spec.color.unwrap_or_else(Color::random));
+                if let Some(team) = spec.team.as_ref().and_then(|name| teams.get(name.as_str())) {
+                    player = world.set_team(&player, Some(*team));
+                }
+
+                let center = spec.position.unwrap_or_else(|| world.find_spawn_position(Mass::default() * spec.cells.mass.get_low()));
+                let spread = spec.cells.spread.sample();
+                for _ in 0..spec.cells.count.sample() {
+                    let pos = center + Position(random() * spread, random() * spread);
+                    let mass = Mass::default() * spec.cells.mass.sample();
+                    world.add_entity(Entity::Cell(Cell::new(mass, pos, Direction::from_radians(random() * std::f64::consts::PI), Speed::default(), Some(player))));
+                }
+
+                if spec.human && human.is_none() {
+                    human = Some(player);
+                }
+                if let Some(profile) = spec.profile.as_ref().and_then(ProfileSpec::resolve) {
+                    profiles.insert(player.get_id(), profile);
+                }
+            }
+        }
+
+        for spec in &self.food {
+            match spec.clusters {
+                Some(clusters) => {
+                    for _ in 0..clusters {
+                        let center = spec.position.unwrap_or_else(|| world.random_position());
+                        for _ in 0..spec.count {
+                            let pos = center + Position(random() * spec.spread, random() * spec.spread);
+                            world.add_entity(Entity::Food(Food::new(Mass::default() * spec.mass.sample(), pos)));
+                        }
+                    }
+                }
+                None => {
+                    for _ in 0..spec.count {
+                        let pos = world.random_position();
+                        world.add_entity(Entity::Food(Food::new(Mass::default() * spec.mass.sample(), pos)));
+                    }
+                }
+            }
+        }
+
+        (world, human, profiles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(bounds: &str, human: &str) -> String {
+        format!(r#"{{ "name": "Test", "bounds": {}, "players": [{{ "name": "Player", {}, "cells": {{ "count": 1, "mass": 1.0, "spread": 0.0 }} }}] }}"#, bounds, human)
+    }
+
+    #[test]
+    fn bounds_have_to_be_positive() {
+        assert!(Scenario::from_json(&scenario("0.5", r#""human": true"#)).is_ok());
+        for bounds in ["0", "-1"] {
+            assert!(matches!(Scenario::from_json(&scenario(bounds, r#""human": true"#)), Err(ScenarioError::InvalidBounds(_))));
+        }
+    }
+
+    #[test]
+    fn exactly_one_human() {
+        assert!(matches!(Scenario::from_json(&scenario("1", r#""human": false"#)), Err(ScenarioError::HumanCount(0))));
+        assert!(matches!(Scenario::from_json(&scenario("1", r#""human": true, "count": 2"#)), Err(ScenarioError::HumanCount(2))));
+    }
+}
@@ -0,0 +1,248 @@
+use crate::*;
+use crate::ai::{Order, Sight};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Scope, AST};
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+// The most operations a bot's script can run each time the bot thinks. A script that goes
+// over is stopped, and the built-in AI plays for the bot on that tick instead.
+pub const SCRIPT_BUDGET: u64 = 50_000;
+
+// Scripts are the files with this extension in the directory they're loaded from
+pub const SCRIPT_EXTENSION: &str = "rhai";
+
+// Where scripts are loaded from when no other directory is given
+pub const SCRIPT_DIRECTORY: &str = "scripts";
+
+// The function every script defines, which gets what the bot can see and gives back what to do
+const ENTRY_POINT: &str = "think";
+
+// How deeply a script's functions can call each other, and the most items a script's
+// strings, arrays and maps can hold
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_SIZE: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ScriptError {
+    // A script or a directory of scripts that couldn't be read
+    Io { path: String, message: String },
+    Syntax { script: String, message: String },
+    // Something that went wrong while a bot was thinking, including going over the budget
+    Runtime { script: String, message: String },
+    // A profile that asks for a script that was never loaded
+    Missing(String),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Io { path, message } => write!(f, "could not read {}: {}", path, message),
+            ScriptError::Syntax { script, message } => write!(f, "could not compile script {:?}: {}", script, message),
+            ScriptError::Runtime { script, message } => write!(f, "script {:?} failed: {}", script, message),
+            ScriptError::Missing(name) => write!(f, "no script named {:?} was loaded", name),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+// Bots written in Rhai, so that new behaviors can be tried out without recompiling the game.
+// Each script defines `think(sight)`, which is handed a read-only `Sight` of what the bot can see,
+// and returns a map with the `direction` to go in (an angle in radians), the `speed` (a multiple of
+// the default speed), and whether to `split`. Returning nothing leaves it to the built-in AI.
+#[derive(Clone)]
+pub struct Scripts {
+    engine: Arc<Engine>,
+    scripts: HashMap<String, Arc<AST>>,
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        Scripts::new()
+    }
+}
+
+impl fmt::Debug for Scripts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Scripts").field("scripts", &self.get_names()).finish()
+    }
+}
+
+impl Scripts {
+    pub fn new() -> Scripts {
+        let mut engine = Engine::new();
+        engine.set_max_operations(SCRIPT_BUDGET);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        engine.set_max_string_size(MAX_SIZE);
+        engine.set_max_array_size(MAX_SIZE);
+        engine.set_max_map_size(MAX_SIZE);
+
+        // Printing goes to stderr, so that it doesn't get mixed up with what the tools write out
+        engine.on_print(|text| eprintln!("{}", text));
+        engine.on_debug(|text, source, position| eprintln!("{} {}: {}", source.unwrap_or("script"), position, text));
+
+        // Positions are `[x, y]` arrays
+        engine.register_fn("distance", |a: Array, b: Array| -> Result<f64, Box<EvalAltResult>> {
+            Ok(to_position(&a)?.distance_to(to_position(&b)?))
+        });
+        engine.register_fn("direction_to", |from: Array, to: Array| -> Result<f64, Box<EvalAltResult>> {
+            Ok(to_position(&from)?.direction_to(to_position(&to)?).0)
+        });
+
+        Scripts {
+            engine: Arc::new(engine),
+            scripts: HashMap::new(),
+        }
+    }
+
+    // Load every script in a directory, each named after its file without the extension.
+    // Scripts that can't be read or compiled are left out, and their errors are given back.
+    pub fn load(directory: impl AsRef<Path>) -> (Scripts, Vec<ScriptError>) {
+        let mut scripts = Scripts::new();
+        let mut errors = Vec::new();
+        let directory = directory.as_ref();
+        let io_error = |path: &Path, err: std::io::Error| ScriptError::Io { path: path.display().to_string(), message: err.to_string() };
+
+        let mut paths = match std::fs::read_dir(directory) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect::<Vec<_>>(),
+            Err(err) => return (scripts, vec![io_error(directory, err)]),
+        };
+        paths.sort();
+        for path in paths.iter().filter(|path| path.extension().is_some_and(|extension| extension == SCRIPT_EXTENSION)) {
+            let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+            let result = std::fs::read_to_string(path)
+                .map_err(|err| io_error(path, err))
+                .and_then(|source| scripts.insert(&name, &source));
+            if let Err(err) = result {
+                errors.push(err);
+            }
+        }
+        (scripts, errors)
+    }
+
+    // Compile a script and add it under a name, in place of any script already called that
+    pub fn insert(&mut self, name: &str, source: &str) -> Result<(), ScriptError> {
+        let syntax = |message: String| ScriptError::Syntax { script: name.to_string(), message };
+        let ast = self.engine.compile(source).map_err(|err| syntax(err.to_string()))?;
+        if !ast.iter_functions().any(|function| function.name == ENTRY_POINT && function.params.len() == 1) {
+            return Err(syntax(format!("there's no `fn {}(sight)`", ENTRY_POINT)));
+        }
+        self.scripts.insert(name.to_string(), Arc::new(ast));
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.scripts.contains_key(name)
+    }
+
+    // Get the name of every script, in alphabetical order
+    pub fn get_names(&self) -> Vec<&str> {
+        let mut names = self.scripts.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    // Ask a script what a bot should do. The script sees `state` as `this`, which is
+    // the bot's own map for keeping anything it likes from one tick to the next.
+    pub fn run(&self, name: &str, sight: &Sight, state: &mut Dynamic) -> Result<Option<Order>, ScriptError> {
+        let ast = self.scripts.get(name).ok_or_else(|| ScriptError::Missing(name.to_string()))?;
+        let runtime = |message: String| ScriptError::Runtime { script: name.to_string(), message };
+
+        let sight = rhai::serde::to_dynamic(sight).map_err(|err| runtime(err.to_string()))?;
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(state);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), ast, ENTRY_POINT, (sight,))
+            .map_err(|err| runtime(err.to_string()))?;
+        to_order(result).map_err(runtime)
+    }
+}
+
+fn to_position(array: &Array) -> Result<Position, Box<EvalAltResult>> {
+    match array.as_slice() {
+        [x, y] => Ok(Position(to_number(x).ok_or("x isn't a number")?, to_number(y).ok_or("y isn't a number")?)),
+        _ => Err("positions are [x, y]".into()),
+    }
+}
+
+// Scripts can use whole numbers wherever a number is expected
+fn to_number(value: &Dynamic) -> Option<f64> {
+    value.as_float().ok().or_else(|| value.as_int().ok().map(|value| value as f64))
+}
+
+// Read what a script returned, which is either nothing or a map with a direction and a speed
+fn to_order(result: Dynamic) -> Result<Option<Order>, String> {
+    if result.is_unit() {
+        return Ok(None);
+    }
+    let map = result.try_cast::<rhai::Map>().ok_or_else(|| format!("`{}` has to return a map, or nothing", ENTRY_POINT))?;
+    let number = |key: &str| map.get(key).and_then(to_number).ok_or_else(|| format!("`{}` has to return a number for `{}`", ENTRY_POINT, key));
+    let split = match map.get("split") {
+        Some(split) => split.as_bool().map_err(|_| format!("`{}` has to return true or false for `split`", ENTRY_POINT))?,
+        None => false,
+    };
+    Ok(Some(Order {
+        direction: Direction::from_radians(number("direction")?),
+        speed: number("speed")?,
+        split,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sight() -> Sight {
+        Sight {
+            bounds: 1.0,
+            center: Position(0.0, 0.0),
+            radius: 0.5,
+            cells: Vec::new(),
+            food: Vec::new(),
+            others: Vec::new(),
+        }
+    }
+
+    fn state() -> Dynamic {
+        Dynamic::from_map(rhai::Map::new())
+    }
+
+    #[test]
+    fn run_a_script() {
+        let mut scripts = Scripts::new();
+        scripts.insert("north", "fn think(sight) { #{ direction: direction_to(sight.center, [0, -1]), speed: 2, split: true } }").unwrap();
+        let order = scripts.run("north", &sight(), &mut state()).unwrap().unwrap();
+        assert!((order.direction.0 - Direction::from_radians(-std::f64::consts::FRAC_PI_2).0).abs() < 1e-9);
+        assert_eq!(order.speed, 2.0);
+        assert!(order.split);
+    }
+
+    #[test]
+    fn scripts_keep_their_state() {
+        let mut scripts = Scripts::new();
+        scripts.insert("counter", "fn think(sight) { this.ticks = if this.ticks == () { 1 } else { this.ticks + 1 }; }").unwrap();
+        let mut state = state();
+        for _ in 0..3 {
+            assert_eq!(scripts.run("counter", &sight(), &mut state).unwrap(), None);
+        }
+        assert_eq!(state.cast::<rhai::Map>()["ticks"].as_int(), Ok(3));
+    }
+
+    #[test]
+    fn scripts_that_go_wrong() {
+        let mut scripts = Scripts::new();
+        assert!(matches!(scripts.insert("broken", "fn think(sight) {"), Err(ScriptError::Syntax { .. })));
+        assert!(matches!(scripts.insert("empty", "let x = 1;"), Err(ScriptError::Syntax { .. })));
+
+        scripts.insert("forever", "fn think(sight) { loop {} }").unwrap();
+        scripts.insert("wrong", "fn think(sight) { 42 }").unwrap();
+        scripts.insert("fails", "fn think(sight) { throw \"oops\"; }").unwrap();
+        for name in ["forever", "wrong", "fails"] {
+            assert!(matches!(scripts.run(name, &sight(), &mut state()), Err(ScriptError::Runtime { .. })));
+        }
+        assert_eq!(scripts.run("nothing", &sight(), &mut state()), Err(ScriptError::Missing("nothing".to_string())));
+    }
+}